@@ -0,0 +1,401 @@
+use crate::variables::Variables;
+
+/// A token of an arithmetic expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Variable(String),
+    /// An operator or a parenthesis, like `+`, `<=` or `(`.
+    Op(&'static str),
+}
+
+/// Every operator, longest first, so `<<` isn't lexed as two `<`.
+const OPERATORS: &[&str] = &[
+    "**=", "<<=", ">>=", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=", "%=", "&=",
+    "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "?", ":", "(", ")", "=",
+];
+
+/// The operators that assign to a variable, like `x = 1` or `x += 2`.
+const ASSIGNMENTS: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "**=", "<<=", ">>=", "&=", "^=", "|="];
+
+/// An arithmetic expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    /// `condition ? then : else`
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// An assignment like `x = 1`, or `x += 2`, which is `x = x + 2`.
+    Assign(&'static str, String, Box<Expr>),
+}
+
+/// Parses a decimal number, a hexadecimal one starting with `0x` or `0X`,
+/// or an octal one starting with `0`, like in C. The number can have a sign.
+fn parse_number(number: &str) -> Result<i64, String> {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", number.strip_prefix('+').unwrap_or(number)),
+    };
+    let (digits, radix) = if let Some(digits) = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+        (digits, 16)
+    } else if unsigned.len() > 1 && unsigned.starts_with('0') {
+        (&unsigned[1..], 8)
+    } else {
+        (unsigned, 10)
+    };
+    // `from_str_radix` accepts a sign of its own, which would allow things like `0x-1`.
+    if !digits.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err(format!("{}: invalid number", number));
+    }
+    // The sign is parsed together with the digits, so the smallest number doesn't overflow.
+    i64::from_str_radix(&format!("{}{}", sign, digits), radix).map_err(|_| format!("{}: invalid number", number))
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..len])?));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            // `$name` is allowed as well, and means the same as `name`.
+            let name_start = if c == '$' { 1 } else { 0 };
+            let len = rest[name_start..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(rest.len(), |len| len + name_start);
+            if len == name_start {
+                return Err(format!("syntax error: invalid token `{}`", c));
+            }
+            tokens.push(Token::Variable(rest[name_start..len].to_string()));
+            len
+        } else {
+            match OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    op.len()
+                }
+                None => return Err(format!("syntax error: invalid token `{}`", c)),
+            }
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Returns how tightly a binary operator binds, or `None` if `op` isn't one.
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | "<=" | ">" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    })
+}
+
+/// A precedence climbing parser for arithmetic expressions,
+/// with the same operators and precedence as in C, plus `**` for exponentiation.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            _ => Err(format!("syntax error: expected `{}`", op)),
+        }
+    }
+
+    /// Parses an assignment, which has the lowest precedence and is right associative,
+    /// or a conditional expression.
+    fn parse_expression(&mut self) -> Result<Expr, String> {
+        if let (Some(Token::Variable(name)), Some(Token::Op(op))) = (self.peek(), self.tokens.get(self.pos + 1)) {
+            if ASSIGNMENTS.contains(op) {
+                let (name, op) = (name.clone(), *op);
+                self.pos += 2;
+                return Ok(Expr::Assign(op, name, Box::new(self.parse_expression()?)));
+            }
+        }
+        self.parse_conditional()
+    }
+
+    /// Parses a conditional expression like `a > b ? a : b`.
+    fn parse_conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.parse_binary(1)?;
+        if self.peek() != Some(&Token::Op("?")) {
+            return Ok(condition);
+        }
+        self.next();
+        let then = self.parse_expression()?;
+        self.expect(":")?;
+        let otherwise = self.parse_conditional()?;
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    /// Parses binary operators that bind at least as tightly as `min_precedence`.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op: &'static str = op;
+            let op_precedence = match precedence(op) {
+                Some(op_precedence) if op_precedence >= min_precedence => op_precedence,
+                _ => break,
+            };
+            self.next();
+            // `**` is right associative, everything else is left associative.
+            let next_precedence = if op == "**" { op_precedence } else { op_precedence + 1 };
+            let rhs = self.parse_binary(next_precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op(op @ ("-" | "+" | "!" | "~"))) => Ok(Expr::Unary(op, Box::new(self.parse_unary()?))),
+            Some(Token::Op("(")) => {
+                let expr = self.parse_expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Variable(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Op(op)) => Err(format!("syntax error: unexpected `{}`", op)),
+            None => Err(String::from("syntax error: expected an operand")),
+        }
+    }
+}
+
+/// Evaluates parsed expressions with the values of the shell's variables.
+struct Evaluator<'a> {
+    variables: &'a mut Variables,
+    /// Whether unset and empty variables are 0, like in `$(( ))`, or an error, like in `calc`.
+    unset_is_zero: bool,
+}
+
+impl Evaluator<'_> {
+    /// Returns the value of a variable, where unset and empty variables are 0 if `unset_is_zero` is set.
+    fn variable_value(&self, name: &str) -> Result<i64, String> {
+        let value = self.variables.get(name).unwrap_or_default().trim();
+        if value.is_empty() {
+            return if self.unset_is_zero {
+                Ok(0)
+            } else {
+                Err(format!("`{}` is not a number or a variable that is set", name))
+            };
+        }
+        parse_number(value).map_err(|_| format!("{}: `{}` is not a number", name, value))
+    }
+
+    /// Evaluates an expression. Overflows wrap around, and `&&`, `||` and `?:`
+    /// only evaluate the operands they need.
+    fn eval(&mut self, expr: &Expr) -> Result<i64, String> {
+        Ok(match expr {
+            Expr::Number(number) => *number,
+            Expr::Variable(name) => self.variable_value(name)?,
+            Expr::Unary(op, operand) => {
+                let operand = self.eval(operand)?;
+                match *op {
+                    "-" => operand.wrapping_neg(),
+                    "!" => (operand == 0) as i64,
+                    "~" => !operand,
+                    _ => operand,
+                }
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.eval(condition)? != 0 {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+            Expr::Binary("&&", lhs, rhs) => (self.eval(lhs)? != 0 && self.eval(rhs)? != 0) as i64,
+            Expr::Binary("||", lhs, rhs) => (self.eval(lhs)? != 0 || self.eval(rhs)? != 0) as i64,
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                apply(op, lhs, rhs)?
+            }
+            Expr::Assign(op, name, value) => {
+                let value = self.eval(value)?;
+                let value = match *op {
+                    "=" => value,
+                    op => apply(&op[..op.len() - 1], self.variable_value(name)?, value)?,
+                };
+                self.variables.set(name, &value.to_string());
+                value
+            }
+        })
+    }
+}
+
+/// Applies a binary operator, other than `&&` and `||`, to two values.
+fn apply(op: &str, lhs: i64, rhs: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err(String::from("division by zero")),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => return Err(String::from("exponent less than 0")),
+        "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "<" => (lhs < rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "&" => lhs & rhs,
+        "^" => lhs ^ rhs,
+        _ => lhs | rhs,
+    })
+}
+
+/// Evaluates an arithmetic expression like `1 + 2 * (x - 3)`, as used in `$(( ))` and `calc`.
+/// Variables can be referred to with or without a `$`, and assigned to with `=`, `+=` and so on.
+/// Unset and empty variables are 0 with `unset_is_zero`, like in `$(( ))`, and an error otherwise.
+pub fn evaluate(expression: &str, variables: &mut Variables, unset_is_zero: bool) -> Result<i64, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        pos: 0,
+    };
+    let expr = parser.parse_expression()?;
+    if let Some(token) = parser.peek() {
+        let token = match token {
+            Token::Number(number) => number.to_string(),
+            Token::Variable(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        };
+        return Err(format!("syntax error: unexpected `{}`", token));
+    }
+    Evaluator {
+        variables,
+        unset_is_zero,
+    }
+    .eval(&expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<i64, String> {
+        evaluate(expression, &mut Variables::default(), true)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(eval("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(eval("10 - 4 - 3").unwrap(), 3);
+        assert_eq!(eval("1 << 2 + 1").unwrap(), 8);
+        assert_eq!(eval("6 & 3 | 8 ^ 1").unwrap(), 11);
+        assert_eq!(eval("1 || 0 && 0").unwrap(), 1);
+        assert_eq!(eval("0 ? 1 : 2 ? 3 : 4").unwrap(), 3);
+    }
+
+    #[test]
+    fn right_associative() {
+        assert_eq!(eval("2 ** 3 ** 2").unwrap(), 512);
+        let mut variables = Variables::default();
+        assert_eq!(evaluate("a = b = 3", &mut variables, true).unwrap(), 3);
+        assert_eq!(variables.get("a"), Some("3"));
+        assert_eq!(variables.get("b"), Some("3"));
+        assert_eq!(evaluate("a += b *= 2", &mut variables, true).unwrap(), 9);
+        assert_eq!(variables.get("a"), Some("9"));
+        assert_eq!(variables.get("b"), Some("6"));
+        assert_eq!(evaluate("(c = 2) + $c", &mut variables, true).unwrap(), 4);
+    }
+
+    #[test]
+    fn unary() {
+        assert_eq!(eval("-3 + 5").unwrap(), 2);
+        assert_eq!(eval("- -3").unwrap(), 3);
+        assert_eq!(eval("-2 ** 2").unwrap(), 4);
+        assert_eq!(eval("!0").unwrap(), 1);
+        assert_eq!(eval("!5").unwrap(), 0);
+        assert_eq!(eval("~0").unwrap(), -1);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(eval("3 < 4").unwrap(), 1);
+        assert_eq!(eval("4 <= 3").unwrap(), 0);
+        assert_eq!(eval("4 >= 4").unwrap(), 1);
+        assert_eq!(eval("1 == 1 && 2 != 2").unwrap(), 0);
+        assert_eq!(eval("1 + 1 == 2").unwrap(), 1);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(eval("1 / 0").unwrap_err(), "division by zero");
+        assert_eq!(eval("1 % (2 - 2)").unwrap_err(), "division by zero");
+        assert_eq!(eval("x /= 0").unwrap_err(), "division by zero");
+        // The division isn't evaluated, so it doesn't fail.
+        assert_eq!(eval("0 && 1 / 0").unwrap(), 0);
+    }
+
+    #[test]
+    fn overflow() {
+        assert_eq!(eval("9223372036854775807 + 1").unwrap(), i64::MIN);
+        assert_eq!(eval("-9223372036854775807 - 2").unwrap(), i64::MAX);
+        assert_eq!(eval("-9223372036854775807 - 1 / -1").unwrap(), -9223372036854775807 + 1);
+        assert_eq!(eval("(-9223372036854775807 - 1) / -1").unwrap(), i64::MIN);
+        assert_eq!(eval("2 ** 64").unwrap(), 0);
+        assert_eq!(eval("9223372036854775808").unwrap_err(), "9223372036854775808: invalid number");
+    }
+
+    #[test]
+    fn bases() {
+        assert_eq!(eval("0x1f + 0XA").unwrap(), 41);
+        assert_eq!(eval("010").unwrap(), 8);
+        assert_eq!(eval("0 + 00").unwrap(), 0);
+        assert_eq!(eval("-0x10").unwrap(), -16);
+        assert_eq!(eval("08").unwrap_err(), "08: invalid number");
+        assert_eq!(eval("0x").unwrap_err(), "0x: invalid number");
+        assert_eq!(eval("12ab").unwrap_err(), "12ab: invalid number");
+        let mut variables = Variables::default();
+        variables.set("x", "0x10");
+        variables.set("y", "-010");
+        variables.set("min", "-9223372036854775808");
+        assert_eq!(evaluate("x + y", &mut variables, true).unwrap(), 8);
+        assert_eq!(evaluate("min", &mut variables, true).unwrap(), i64::MIN);
+    }
+
+    #[test]
+    fn variables() {
+        let mut variables = Variables::default();
+        variables.set("x", " 4 ");
+        variables.set("word", "abc");
+        assert_eq!(evaluate("x * $x", &mut variables, true).unwrap(), 16);
+        assert_eq!(evaluate("unset + 1", &mut variables, true).unwrap(), 1);
+        assert!(evaluate("unset + 1", &mut variables, false).is_err());
+        assert_eq!(evaluate("word", &mut variables, true).unwrap_err(), "word: `abc` is not a number");
+    }
+}
@@ -0,0 +1,321 @@
+use crate::lexer::{ParamOp, Parameter, RedirectOp, ReplaceMode, Word, WordPart};
+use crate::parser::{
+    AndOrList, Assignment, CaseArm, Command, CompoundCommand, Connector, FunctionDefinition, Pipeline, Program,
+    Redirect, SimpleCommand,
+};
+
+/// A JSON value, just enough to print the syntax tree for `--dump-ast`.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u32),
+    String(String),
+    Array(Vec<Json>),
+    /// The fields are printed in the order they were given.
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn string(text: &str) -> Json {
+        Json::String(text.to_string())
+    }
+
+    fn array<T: ToJson>(items: &[T]) -> Json {
+        Json::Array(items.iter().map(ToJson::to_json).collect())
+    }
+
+    /// An object with its `type` first, so every node says what it is.
+    fn node(kind: &str, mut fields: Vec<(&'static str, Json)>) -> Json {
+        fields.insert(0, ("type", Json::string(kind)));
+        Json::Object(fields)
+    }
+
+    /// Writes the value with two spaces of indentation for every level.
+    fn write(&self, output: &mut String, indent: usize) {
+        match self {
+            Json::Null => output.push_str("null"),
+            Json::Bool(value) => output.push_str(&value.to_string()),
+            Json::Number(value) => output.push_str(&value.to_string()),
+            Json::String(text) => write_string(output, text),
+            Json::Array(items) if items.is_empty() => output.push_str("[]"),
+            Json::Array(items) => {
+                output.push('[');
+                for (idx, item) in items.iter().enumerate() {
+                    output.push_str(if idx == 0 { "\n" } else { ",\n" });
+                    output.push_str(&"  ".repeat(indent + 1));
+                    item.write(output, indent + 1);
+                }
+                output.push('\n');
+                output.push_str(&"  ".repeat(indent));
+                output.push(']');
+            }
+            Json::Object(fields) => {
+                output.push('{');
+                for (idx, (name, value)) in fields.iter().enumerate() {
+                    output.push_str(if idx == 0 { "\n" } else { ",\n" });
+                    output.push_str(&"  ".repeat(indent + 1));
+                    write_string(output, name);
+                    output.push_str(": ");
+                    value.write(output, indent + 1);
+                }
+                output.push('\n');
+                output.push_str(&"  ".repeat(indent));
+                output.push('}');
+            }
+        }
+    }
+}
+
+/// Writes `text` as a JSON string, with quotes around it and special characters escaped.
+fn write_string(output: &mut String, text: &str) {
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+trait ToJson {
+    fn to_json(&self) -> Json;
+}
+
+/// A program is the lists in it.
+impl ToJson for Program {
+    fn to_json(&self) -> Json {
+        Json::array(&self.lists)
+    }
+}
+
+impl ToJson for AndOrList {
+    fn to_json(&self) -> Json {
+        let rest = self
+            .rest
+            .iter()
+            .map(|(connector, pipeline)| {
+                let connector = match connector {
+                    Connector::And => "&&",
+                    Connector::Or => "||",
+                };
+                Json::Object(vec![("connector", Json::string(connector)), ("pipeline", pipeline.to_json())])
+            })
+            .collect();
+        Json::node(
+            "and_or",
+            vec![
+                ("first", self.first.to_json()),
+                ("rest", Json::Array(rest)),
+                ("background", Json::Bool(self.background)),
+            ],
+        )
+    }
+}
+
+impl ToJson for Pipeline {
+    fn to_json(&self) -> Json {
+        Json::node(
+            "pipeline",
+            vec![
+                ("text", Json::string(&self.text)),
+                ("negated", Json::Bool(self.negated)),
+                ("commands", Json::array(&self.commands)),
+            ],
+        )
+    }
+}
+
+impl ToJson for Command {
+    fn to_json(&self) -> Json {
+        match self {
+            Command::Simple(command) => command.to_json(),
+            Command::Compound(command, redirects) => compound_to_json(command, redirects),
+            Command::Function(definition) => definition.to_json(),
+        }
+    }
+}
+
+impl ToJson for SimpleCommand {
+    fn to_json(&self) -> Json {
+        Json::node(
+            "simple",
+            vec![
+                ("assignments", Json::array(&self.assignments)),
+                ("words", Json::array(&self.words)),
+                ("redirects", Json::array(&self.redirects)),
+            ],
+        )
+    }
+}
+
+impl ToJson for Assignment {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![("name", Json::string(&self.name)), ("value", self.value.to_json())])
+    }
+}
+
+impl ToJson for Redirect {
+    fn to_json(&self) -> Json {
+        let op = match self.op {
+            RedirectOp::Input => "<",
+            RedirectOp::ReadWrite => "<>",
+            RedirectOp::Overwrite => ">",
+            RedirectOp::Clobber => ">|",
+            RedirectOp::Append => ">>",
+            RedirectOp::DupInput => "<&",
+            RedirectOp::DupOutput => ">&",
+            RedirectOp::OutputAndError => "&>",
+            RedirectOp::AppendOutputAndError => "&>>",
+        };
+        Json::Object(vec![
+            ("fd", self.fd.map_or(Json::Null, Json::Number)),
+            ("op", Json::string(op)),
+            ("target", self.target.to_json()),
+        ])
+    }
+}
+
+/// A compound command, with the redirections that were written after it.
+fn compound_to_json(command: &CompoundCommand, redirects: &[Redirect]) -> Json {
+    let mut json = match command {
+        CompoundCommand::If { branches, else_branch } => {
+            let branches = branches
+                .iter()
+                .map(|(condition, body)| Json::Object(vec![("condition", condition.to_json()), ("body", body.to_json())]))
+                .collect();
+            Json::node(
+                "if",
+                vec![
+                    ("branches", Json::Array(branches)),
+                    ("else", else_branch.as_ref().map_or(Json::Null, ToJson::to_json)),
+                ],
+            )
+        }
+        CompoundCommand::While { condition, body, until } => Json::node(
+            if *until { "until" } else { "while" },
+            vec![("condition", condition.to_json()), ("body", body.to_json())],
+        ),
+        CompoundCommand::For { name, words, body } => Json::node(
+            "for",
+            vec![
+                ("name", Json::string(name)),
+                ("words", words.as_ref().map_or(Json::Null, |words| Json::array(words))),
+                ("body", body.to_json()),
+            ],
+        ),
+        CompoundCommand::Case { word, arms } => {
+            Json::node("case", vec![("word", word.to_json()), ("arms", Json::array(arms))])
+        }
+        CompoundCommand::BraceGroup(body) => Json::node("brace_group", vec![("body", body.to_json())]),
+    };
+    if let Json::Object(fields) = &mut json {
+        fields.push(("redirects", Json::array(redirects)));
+    }
+    json
+}
+
+impl ToJson for CaseArm {
+    fn to_json(&self) -> Json {
+        Json::Object(vec![("patterns", Json::array(&self.patterns)), ("body", self.body.to_json())])
+    }
+}
+
+impl ToJson for FunctionDefinition {
+    fn to_json(&self) -> Json {
+        Json::node(
+            "function",
+            vec![
+                ("name", Json::string(&self.name)),
+                ("body", compound_to_json(&self.body, &[])),
+                ("redirects", Json::array(&self.redirects)),
+            ],
+        )
+    }
+}
+
+/// A word is the parts it is made of.
+impl ToJson for Word {
+    fn to_json(&self) -> Json {
+        Json::array(&self.parts)
+    }
+}
+
+impl ToJson for WordPart {
+    fn to_json(&self) -> Json {
+        match self {
+            WordPart::Literal(text) => Json::node("literal", vec![("text", Json::string(text))]),
+            WordPart::Quoted(text) => Json::node("quoted", vec![("text", Json::string(text))]),
+            WordPart::DoubleQuoted(parts) => Json::node("double_quoted", vec![("parts", Json::array(parts))]),
+            WordPart::Param(parameter) => parameter.to_json(),
+            WordPart::Command(program) => Json::node("command_substitution", vec![("program", program.to_json())]),
+            WordPart::Arithmetic(word) => Json::node("arithmetic", vec![("expression", word.to_json())]),
+        }
+    }
+}
+
+impl ToJson for Parameter {
+    fn to_json(&self) -> Json {
+        Json::node(
+            "parameter",
+            vec![
+                ("name", Json::string(&self.name)),
+                ("op", self.op.as_ref().map_or(Json::Null, ToJson::to_json)),
+            ],
+        )
+    }
+}
+
+impl ToJson for ParamOp {
+    fn to_json(&self) -> Json {
+        let with_word = |kind, colon: &bool, word: &Word| {
+            Json::node(kind, vec![("colon", Json::Bool(*colon)), ("word", word.to_json())])
+        };
+        let with_pattern = |kind, longest: &bool, pattern: &Word| {
+            Json::node(kind, vec![("longest", Json::Bool(*longest)), ("pattern", pattern.to_json())])
+        };
+        match self {
+            ParamOp::Length => Json::node("length", Vec::new()),
+            ParamOp::Default { colon, word } => with_word("default", colon, word),
+            ParamOp::Assign { colon, word } => with_word("assign", colon, word),
+            ParamOp::Error { colon, word } => with_word("error", colon, word),
+            ParamOp::Alternative { colon, word } => with_word("alternative", colon, word),
+            ParamOp::RemovePrefix { longest, pattern } => with_pattern("remove_prefix", longest, pattern),
+            ParamOp::RemoveSuffix { longest, pattern } => with_pattern("remove_suffix", longest, pattern),
+            ParamOp::Replace {
+                mode,
+                pattern,
+                replacement,
+            } => {
+                let mode = match mode {
+                    ReplaceMode::First => "first",
+                    ReplaceMode::All => "all",
+                    ReplaceMode::Prefix => "prefix",
+                    ReplaceMode::Suffix => "suffix",
+                };
+                Json::node(
+                    "replace",
+                    vec![
+                        ("mode", Json::string(mode)),
+                        ("pattern", pattern.to_json()),
+                        ("replacement", replacement.to_json()),
+                    ],
+                )
+            }
+        }
+    }
+}
+
+/// Formats the syntax tree of `program` as JSON, for `--dump-ast`.
+/// Every node has a `type`, like `pipeline`, `simple` or `literal`.
+/// Lists of commands, like a program or the body of a loop, and words are arrays.
+pub fn dump(program: &Program) -> String {
+    let mut output = String::new();
+    program.to_json().write(&mut output, 0);
+    output
+}
@@ -0,0 +1,156 @@
+use crate::lexer::{Word, WordPart};
+
+/// A piece of a word during brace expansion: either an unquoted character,
+/// which can be part of a brace expression, or a part that is kept as-is.
+#[derive(Debug, Clone)]
+enum Item {
+    Char(char),
+    Part(WordPart),
+}
+
+fn to_items(word: &Word) -> Vec<Item> {
+    let mut items = Vec::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => items.extend(text.chars().map(Item::Char)),
+            part => items.push(Item::Part(part.clone())),
+        }
+    }
+    items
+}
+
+fn to_word(items: &[Item]) -> Word {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    for item in items {
+        match item {
+            Item::Char(c) => literal.push(*c),
+            Item::Part(part) => {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(part.clone());
+            }
+        }
+    }
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal(literal));
+    }
+    Word { parts }
+}
+
+/// Returns the text of `items` if they are all characters.
+fn to_text(items: &[Item]) -> Option<String> {
+    items
+        .iter()
+        .map(|item| match item {
+            Item::Char(c) => Some(*c),
+            Item::Part(_) => None,
+        })
+        .collect()
+}
+
+/// Expands a sequence like `1..10`, `01..10..2` or `a..e`, returning `None` if it isn't one, or is too long.
+/// Numbers are zero-padded to the same width if either end has a leading zero.
+fn expand_sequence(text: &str) -> Option<Vec<String>> {
+    let mut ends = text.split("..");
+    let (start, end) = (ends.next()?, ends.next()?);
+    let step = match ends.next() {
+        Some(step) => step.parse::<i64>().ok()?.checked_abs()?.max(1),
+        None => 1,
+    };
+    if ends.next().is_some() {
+        return None;
+    }
+    if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        let has_leading_zero = |number: &str| number.trim_start_matches('-').starts_with('0') && number.len() > 1;
+        let width = if has_leading_zero(start) || has_leading_zero(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        return Some(range(first, last, step)?.map(|number| format!("{:0width$}", number, width = width)).collect());
+    }
+    let (mut start_chars, mut end_chars) = (start.chars(), end.chars());
+    match (start_chars.next(), start_chars.next(), end_chars.next(), end_chars.next()) {
+        (Some(first), None, Some(last), None) if first.is_ascii_alphabetic() && last.is_ascii_alphabetic() => Some(
+            range(first as i64, last as i64, step)?
+                .map(|c| (c as u8 as char).to_string())
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// The most elements a sequence can have. Longer ones, like `{1..99999999999}`, are left as they are.
+const MAX_SEQUENCE_LENGTH: u64 = 100_000;
+
+/// Counts from `first` to `last` by `step`, downwards if `last` is smaller.
+/// Returns `None` if that would be more than `MAX_SEQUENCE_LENGTH` numbers.
+fn range(first: i64, last: i64, step: i64) -> Option<impl Iterator<Item = i64>> {
+    let count = first.abs_diff(last) / step as u64;
+    if count >= MAX_SEQUENCE_LENGTH {
+        return None;
+    }
+    let step = if last < first { -step } else { step };
+    Some((0..=count as i64).map(move |idx| first + idx * step))
+}
+
+/// Finds the first brace expression in `items`, returning where it starts and ends,
+/// and the alternatives inside of it. Braces without a `,` or a sequence in them are skipped.
+fn find_braces(items: &[Item]) -> Option<(usize, usize, Vec<Vec<Item>>)> {
+    for (open, item) in items.iter().enumerate() {
+        if !matches!(item, Item::Char('{')) {
+            continue;
+        }
+        let mut depth = 0;
+        let mut commas = vec![open];
+        for (idx, item) in items.iter().enumerate().skip(open + 1) {
+            match item {
+                Item::Char('{') => depth += 1,
+                Item::Char('}') if depth > 0 => depth -= 1,
+                Item::Char(',') if depth == 0 => commas.push(idx),
+                Item::Char('}') => {
+                    let alternatives: Vec<Vec<Item>> = if commas.len() > 1 {
+                        commas.push(idx);
+                        commas.windows(2).map(|bounds| items[bounds[0] + 1..bounds[1]].to_vec()).collect()
+                    } else {
+                        match to_text(&items[open + 1..idx]).and_then(|text| expand_sequence(&text)) {
+                            Some(sequence) => sequence
+                                .iter()
+                                .map(|text| text.chars().map(Item::Char).collect())
+                                .collect(),
+                            None => break,
+                        }
+                    };
+                    return Some((open, idx, alternatives));
+                }
+                _ => (),
+            }
+        }
+    }
+    None
+}
+
+fn expand_items(items: Vec<Item>, expanded: &mut Vec<Vec<Item>>) {
+    match find_braces(&items) {
+        Some((open, close, alternatives)) => {
+            for alternative in alternatives {
+                let mut word = items[..open].to_vec();
+                word.extend(alternative);
+                word.extend_from_slice(&items[close + 1..]);
+                expand_items(word, expanded);
+            }
+        }
+        None => expanded.push(items),
+    }
+}
+
+/// Expands the brace expressions in a word, like `a{b,c}d` into `abd acd`,
+/// `{1..3}` into `1 2 3` or `{a..e..2}` into `a c e`.
+/// Braces inside of quotes or variables are left alone.
+pub fn expand_braces(word: &Word) -> Vec<Word> {
+    let mut expanded = Vec::new();
+    expand_items(to_items(word), &mut expanded);
+    expanded.iter().map(|items| to_word(items)).collect()
+}
@@ -1,48 +1,52 @@
-use crate::commands::is_piped;
-
-/// Get the calculator vars (`math_op`, `first_number`, `second_number`) for calc.
-pub fn get_calc_vars(problem: &str) -> (&str, i32, i32) {
-    let math_op = if problem.contains('x') {
-        "x"
-    } else if problem.contains('*') {
-        "*"
-    } else if problem.contains('/') {
-        "/"
-    } else if problem.contains('%') {
-        "%"
-    } else if problem.contains('+') {
-        "+"
-    } else if problem.contains('-') {
-        "-"
-    } else {
-        ""
-    };
-    let problem_vector: Vec<&str> = problem.split(math_op).collect();
-    let first_number: i32 = problem_vector[0].parse().unwrap();
-    let second_number: i32 = problem_vector[1].parse().unwrap();
-    (math_op, first_number, second_number)
-}
+use crate::arithmetic::evaluate;
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use crate::variables::Variables;
 
 /// Takes the `args` part of a `ShellCommand` struct,
 /// and tries to evaluate the given mathematical expression,
-/// returning a String with the result.
-pub fn calc(args: &[String]) -> String {
-    let mut output = String::new();
-    is_piped(args, "calc");
-    let problem = args.concat();
-    let (math_op, first_number, second_number) = get_calc_vars(&problem);
-    match math_op {
-        // Multiplication
-        "x" | "*" => output.push_str(format!("{}", first_number * second_number).as_str()),
-        // Division
-        "/" => output.push_str(format!("{}", first_number / second_number).as_str()),
-        // Division with remainder
-        "%" => output.push_str(format!("{}", first_number % second_number).as_str()),
-        // Addition
-        "+" => output.push_str(format!("{}", first_number + second_number).as_str()),
-        // Subtraction
-        "-" => output.push_str(format!("{}", first_number - second_number).as_str()),
-        _ => output.push_str(format!("Error, '{}' is an unsupported operation.", math_op).as_str()),
+/// returning a String with the result, or an error.
+/// Besides `*`, an `x` between two numbers can be used for multiplication, e.g. `2x4`.
+pub fn calc(args: &[String], variables: &mut Variables) -> Result<String, ShellError> {
+    let problem = args.join(" ");
+    let mut expression = String::new();
+    let mut previous = ' ';
+    for c in problem.chars() {
+        if c == 'x' && (previous.is_ascii_digit() || previous == ')') {
+            expression.push('*');
+        } else {
+            expression.push(c);
+        }
+        if !c.is_whitespace() {
+            previous = c;
+        }
+    }
+    // Unlike in `$(( ))`, a word that isn't a number or a set variable, like in `calc abc`, is an error.
+    evaluate(&expression, variables, false)
+        .map(|result| result.to_string())
+        .map_err(|why| ShellError::invalid_argument("calc", &problem, why))
+}
+
+pub struct Calc;
+
+impl Builtin for Calc {
+    fn name(&self) -> &'static str {
+        "calc"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Evaluates an arithmetic expression, the same way as `$(( ))` does.\n\
+            Supports +, -, *, /, %, ** and parentheses, comparisons like < and ==,\n\
+            bitwise operators like & and <<, logical operators like && and !, variables,\n\
+            and assignments like x = 1 or x += 2.\n\
+            `x` can be used for multiplication too. E.g. 1+2*3, -(4-6)/2, 2x4, x * 2"
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(calc(args, &mut shell_state.variables).map(|output| output + "\n"), stdout, stderr)
     }
-    output
 }
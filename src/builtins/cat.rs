@@ -1,30 +1,77 @@
-use crate::commands::is_piped;
-use sflib::{line, read};
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use sflib::read;
+use std::path::Path;
 
-pub fn cat(args: &[String]) -> String {
-    is_piped(args, "cat");
-    match args[0].as_str() {
-        "-l" => {
-            let line_number = args[1].parse::<usize>().unwrap() - 1; // -1 to account for 0-indexing.
-            line(&args[2], line_number).unwrap()
+/// Reads a file for `cat`, where relative paths are relative to `cwd`.
+fn read_file(cwd: &Path, path: &str) -> Result<String, ShellError> {
+    read(&cwd.join(path).to_string_lossy()).map_err(|why| ShellError::io(format!("cat: {}", path), why))
+}
+
+/// Returns the contents of the files, only one line of a file with `-l NUMBER`,
+/// or every line of a file with its number in front of it with `-n`.
+pub fn cat(cwd: &Path, args: &[String]) -> Result<String, ShellError> {
+    match args {
+        [flag, number, path] if flag == "-l" => {
+            let line_number = match number.parse::<usize>() {
+                Ok(number) if number > 0 => number - 1, // -1 to account for 0-indexing.
+                _ => return Err(ShellError::invalid_argument("cat", number, "not a valid line number")),
+            };
+            let output = read_file(cwd, path)?;
+            match output.split('\n').nth(line_number) {
+                Some(line) => Ok(line.to_string()),
+                None => {
+                    let reason = format!("{} doesn't have that many lines", path);
+                    Err(ShellError::invalid_argument("cat", number, reason))
+                }
+            }
         }
-        "-n" => {
+        [flag] | [flag, _] if flag == "-l" => Err(ShellError::MissingArgument {
+            command: "cat",
+            argument: "line number or file",
+        }),
+        [flag, path] if flag == "-n" => {
             let mut final_output = String::new();
-            let output = read(&args[1]).unwrap();
+            let output = read_file(cwd, path)?;
             let output_vec = output.split('\n');
             for (idx, line) in output_vec.enumerate() {
                 let string = format!("{} {}\n", idx, line);
                 final_output.push_str(&string);
             }
-            final_output
+            Ok(final_output)
         }
-        _ => {
-            let contents = read(&args[0]);
-            if contents.is_err() {
-                format!("[cat: '{}']: No such file or directory.", args[0])
-            } else {
-                contents.unwrap()
-            }
+        [flag] if flag == "-n" => Err(ShellError::MissingArgument {
+            command: "cat",
+            argument: "file",
+        }),
+        paths => Ok(paths.iter().map(|path| read_file(cwd, path)).collect::<Result<Vec<_>, _>>()?.concat()),
+    }
+}
+
+pub struct Cat;
+
+impl Builtin for Cat {
+    fn name(&self) -> &'static str {
+        "cat"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Support printing file output, and additionally showing line numbers with `-n`.\n\
+            Without any files, stdin is printed instead."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], stdin: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        // Without any files, stdin is copied to stdout, e.g. in `ls | cat`.
+        if args.is_empty() {
+            return match std::io::copy(stdin, stdout) {
+                Ok(_) => ExitStatus::SUCCESS,
+                Err(why) => write_output(Err(ShellError::io("cat", why)), stdout, stderr),
+            };
         }
+        write_output(cat(&shell_state.cwd, args).map(|output| output + "\n"), stdout, stderr)
     }
 }
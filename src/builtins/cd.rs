@@ -1,43 +1,62 @@
-use crate::commands::ShellCommand;
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
 use crate::shared_functions::ShellState;
-use std::env::current_dir;
+use crate::streams::{Input, Output};
+use std::path::Path;
 
 /// Helper for cd, to actually change the dirctory.
-fn cd_helper(dir: &str) {
-    let path = std::path::Path::new(dir);
-    match std::env::set_current_dir(&path) {
-        Ok(()) => (),
-        Err(_) => println!("Failed to change directory to '{}'", path.display()),
+/// Only the shell itself changes the working directory of the process,
+/// a subshell on another thread just changes its own.
+fn cd_helper(shell_state: &mut ShellState, dir: &Path) -> Result<(), ShellError> {
+    let error = |why| ShellError::io(format!("cd: {}", dir.display()), why);
+    let path = shell_state.cwd.join(dir).canonicalize().map_err(error)?;
+    if !path.is_dir() {
+        return Err(error(std::io::Error::from_raw_os_error(libc::ENOTDIR)));
     }
+    if !shell_state.subshell {
+        std::env::set_current_dir(&path).map_err(error)?;
+    }
+    shell_state.cwd = path;
+    Ok(())
 }
 
 /// Used to change directory.
-/// Takes a `ShellState` and `ShellCommand`.
+/// Takes a `ShellState` and the arguments of the command.
 /// `ShellState` is used to realize `cd -` fuctionality,
 /// but can be used for other options in the future.
-pub fn cd(shell_state: &mut ShellState, command: &ShellCommand) {
-    if command.args.is_empty() {
-        shell_state.cd_prev_dir = Some(current_dir().unwrap());
-        let user = std::env::var("USER").unwrap();
-        let home = ["/home/", user.as_str()].concat();
-        cd_helper(&home);
-    } else if command.args[0] == "-" {
-        if shell_state.cd_prev_dir.is_none() {
-            println!("No previous dir found");
-            return;
-        }
-        // unwrap can be safely used here, because function would've returned
-        // if cd_prev_dir is None
-        match &shell_state.cd_prev_dir.as_ref().unwrap().to_str() {
-            Some(path) => cd_helper(path),
-            None => {
-                println!("Could not convert Path to String (src/buildins.rs in function cd)");
-                shell_state.cd_prev_dir = None;
-            }
+pub fn cd(shell_state: &mut ShellState, args: &[String]) -> Result<(), ShellError> {
+    let current = shell_state.cwd.clone();
+    match args {
+        [] => {
+            let home = shell_state.variables.get("HOME").unwrap_or(&shell_state.home).to_string();
+            cd_helper(shell_state, Path::new(&home))?;
         }
-        shell_state.cd_prev_dir = Some(current_dir().unwrap());
-    } else {
-        shell_state.cd_prev_dir = Some(current_dir().unwrap());
-        cd_helper(&command.args[0]);
+        [dir] if dir == "-" => match shell_state.cd_prev_dir.clone() {
+            Some(dir) => cd_helper(shell_state, &dir)?,
+            None => return Err(ShellError::Failed(String::from("cd: No previous dir found"))),
+        },
+        [dir] => cd_helper(shell_state, Path::new(dir))?,
+        _ => return Err(ShellError::TooManyArguments("cd")),
+    }
+    shell_state.cd_prev_dir = Some(current);
+    Ok(())
+}
+
+pub struct Cd;
+
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Takes a absolute or relative path and changes directory to it.\n\
+            `cd -` will take you to your previous dir."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(cd(shell_state, args).map(|()| String::new()), stdout, stderr)
     }
 }
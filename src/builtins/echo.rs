@@ -1,13 +1,27 @@
-use crate::commands::is_piped;
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
 
 /// Just like you know it. Takes the args part of `ShellCommand` and prints them.
 pub fn echo(args: &[String]) -> String {
-    let mut output = String::new();
-    is_piped(args, "echo");
-    for arg in args {
-        // TODO: Support other escape sequences.
-        let arg_to_push = arg.replace("\\n", "\n"); // Needed to replace \n with newline.
-        output.push_str(format!("{} ", arg_to_push).as_str());
+    // TODO: Support other escape sequences.
+    // Needed to replace \n with newline.
+    args.iter().map(|arg| arg.replace("\\n", "\n")).collect::<Vec<_>>().join(" ")
+}
+
+pub struct Echo;
+
+impl Builtin for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn help(&self) -> &'static str {
+        "Takes n amount of arguments and prints them to stdout."
+    }
+
+    fn run(&self, _: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(Ok(echo(args) + "\n"), stdout, stderr)
     }
-    output
 }
@@ -0,0 +1,44 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::{cmd, return_shellcommand, ExitStatus};
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output, Streams};
+
+/// Returns every exported variable as `NAME=value`, one per line.
+pub fn env(shell_state: &ShellState) -> String {
+    shell_state
+        .variables
+        .exported()
+        .map(|(name, value)| format!("{}={}\n", name, value))
+        .collect()
+}
+
+pub struct Env;
+
+impl Builtin for Env {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn help(&self) -> &'static str {
+        "Prints every exported variable as `NAME=value`.\nWith arguments, like `env FOO=1 cmd`, the external `env` is ran instead."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], stdin: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        if args.is_empty() {
+            return write_output(Ok(env(shell_state)), stdout, stderr);
+        }
+        let streams = (|| {
+            Ok(Streams {
+                stdin: stdin.try_clone()?,
+                stdout: stdout.try_clone()?,
+                stderr: stderr.try_clone()?,
+                extra: Vec::new(),
+            })
+        })();
+        match streams {
+            Ok(streams) => cmd(shell_state, &return_shellcommand(String::from("env"), args.to_vec(), Vec::new()), streams),
+            Err(why) => ShellError::io("env: could not duplicate the streams", why).report(),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use crate::variables::is_valid_name;
+
+/// Marks variables as exported, so they are passed to external commands.
+/// Takes `NAME` or `NAME=value` arguments, and lists all exported variables if there are none.
+pub fn export(shell_state: &mut ShellState, args: &[String]) -> Result<String, ShellError> {
+    if args.is_empty() {
+        let mut output = String::new();
+        for (name, variable) in shell_state.variables.iter().filter(|(_, variable)| variable.exported) {
+            match &variable.value {
+                Some(value) => output.push_str(&format!("export {}=\"{}\"\n", name, value)),
+                None => output.push_str(&format!("export {}\n", name)),
+            }
+        }
+        return Ok(output);
+    }
+    let mut invalid = Vec::new();
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if is_valid_name(name) {
+            shell_state.variables.export(name, value);
+        } else {
+            invalid.push(ShellError::invalid_argument("export", arg, "not a valid identifier"));
+        }
+    }
+    ShellError::combine(invalid).map(|()| String::new())
+}
+
+pub struct Export;
+
+impl Builtin for Export {
+    fn name(&self) -> &'static str {
+        "export"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Marks variables as exported, so they are passed to external commands.\n\
+            Takes `NAME` or `NAME=value` arguments, and lists all exported variables if there are none."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(export(shell_state, args), stdout, stderr)
+    }
+}
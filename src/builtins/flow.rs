@@ -0,0 +1,142 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::executor::ControlFlow;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+
+/// Implements `break` and `continue`, which take the number of enclosing loops
+/// to leave or continue, defaulting to 1.
+/// A number bigger than the number of enclosing loops means the outermost one.
+pub fn loop_control(shell_state: &mut ShellState, name: &'static str, args: &[String]) -> Result<(), ShellError> {
+    let count = match args.first() {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(count) if count > 0 => count,
+            _ => return Err(ShellError::invalid_argument(name, arg, "loop count out of range")),
+        },
+        None => 1,
+    };
+    if shell_state.loop_depth == 0 {
+        return Err(ShellError::Failed(format!("{}: only meaningful in a loop", name)));
+    }
+    let count = count.min(shell_state.loop_depth);
+    shell_state.control_flow = Some(if name == "break" {
+        ControlFlow::Break(count)
+    } else {
+        ControlFlow::Continue(count)
+    });
+    Ok(())
+}
+
+/// Implements `return`, which leaves the function that is running.
+/// The exit status of the function is the given one, or the one of the last command.
+pub fn return_from_function(shell_state: &mut ShellState, args: &[String]) -> Result<ExitStatus, ShellError> {
+    if shell_state.function_depth == 0 {
+        return Err(ShellError::Failed(String::from("return: can only be used in a function")));
+    }
+    let status = match args.first() {
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(code) => ExitStatus::from_code(code & 0xff),
+            Err(_) => return Err(ShellError::invalid_argument("return", arg, "numeric argument required")),
+        },
+        None => shell_state.exit_status,
+    };
+    shell_state.control_flow = Some(ControlFlow::Return);
+    Ok(status)
+}
+
+/// Implements `exit`, which exits the shell once every command and function that is running was left.
+/// The exit code is the given one, or the one of the last command.
+/// With job control, the first `exit` only warns if there are jobs left.
+pub fn exit(shell_state: &mut ShellState, args: &[String]) -> Result<ExitStatus, ShellError> {
+    let code = match args {
+        [] => shell_state.exit_status.code,
+        [arg] => match arg.parse::<i32>() {
+            Ok(code) => code & 0xff,
+            Err(_) => {
+                shell_state.control_flow = Some(ControlFlow::Exit(2));
+                return Err(ShellError::invalid_argument("exit", arg, "numeric argument required"));
+            }
+        },
+        _ => return Err(ShellError::TooManyArguments("exit")),
+    };
+    if shell_state.job_control {
+        if let Some(warning) = shell_state.jobs.exit_warning() {
+            return Err(ShellError::Failed(format!("exit: {}", warning)));
+        }
+    }
+    shell_state.control_flow = Some(ControlFlow::Exit(code));
+    Ok(ExitStatus::from_code(code))
+}
+
+pub struct Break;
+
+impl Builtin for Break {
+    fn name(&self) -> &'static str {
+        "break"
+    }
+
+    fn help(&self) -> &'static str {
+        "Leaves the innermost loop, or the n-th enclosing loop with `break n`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(loop_control(shell_state, "break", args).map(|()| String::new()), stdout, stderr)
+    }
+}
+
+pub struct Continue;
+
+impl Builtin for Continue {
+    fn name(&self) -> &'static str {
+        "continue"
+    }
+
+    fn help(&self) -> &'static str {
+        "Starts the next iteration of the innermost loop, or of the n-th enclosing loop with `continue n`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(loop_control(shell_state, "continue", args).map(|()| String::new()), stdout, stderr)
+    }
+}
+
+pub struct Exit;
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Exits the shell with the given exit code, or the one of the last command.\n\
+            If there are jobs left, the first `exit` only warns about them."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        match exit(shell_state, args) {
+            Ok(status) => status,
+            Err(why) => write_output(Err(why), stdout, stderr),
+        }
+    }
+}
+
+pub struct Return;
+
+impl Builtin for Return {
+    fn name(&self) -> &'static str {
+        "return"
+    }
+
+    fn help(&self) -> &'static str {
+        "Leaves the current function, with the given exit status or the one of the last command."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        match return_from_function(shell_state, args) {
+            Ok(status) => status,
+            Err(why) => write_output(Err(why), stdout, stderr),
+        }
+    }
+}
@@ -1,72 +1,89 @@
-/// Prints a list of builtin commands.
-pub fn help(args: &[String]) {
+use crate::builtins::{find, write_output, Builtin, BUILTINS};
+use crate::commands::ExitStatus;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+
+/// Help topics about the shell itself, which aren't builtins.
+const TOPICS: &[(&str, &str)] = &[
+    (
+        "control-flow",
+        "\
+            if COMMANDS; then COMMANDS; elif COMMANDS; then COMMANDS; else COMMANDS; fi\n\
+            while COMMANDS; do COMMANDS; done\n\
+            until COMMANDS; do COMMANDS; done\n\
+            for NAME in WORDS; do COMMANDS; done\n\
+            case WORD in PATTERN|PATTERN) COMMANDS ;; esac\n\
+            A `!` in front of a pipeline inverts its exit status, e.g. `if ! grep -q x file; then ...`.\n\
+            Commands can span multiple lines, the prompt changes to `> ` until they are complete.",
+    ),
+    (
+        "functions",
+        "\
+            Functions are defined with `name() { COMMANDS; }`, and called like any other command.\n\
+            The arguments are available as $1, $2, ..., $@ and $#.\n\
+            Use `local` for variables that only exist while the function runs,\n\
+            and `return` to leave the function early.",
+    ),
+    (
+        "prompt",
+        "\
+            Can be set to a static string, by just setting the string in the PROMPT env variable,\n\
+            or can be set to a dynamic prompt, by including a command to be executed, by delimiting it with %(),\
+            in the prompt string.\n\
+            Variables are also supported, such as %{U} for user, %{CL} for CWD, etc.\n\
+            e.G.: `%(whoami)E@%(hostname)> `. This will, for my case, produce `zeno@aether> `.",
+    ),
+];
+
+/// Lists every builtin and topic.
+fn list() -> String {
+    let mut output = String::from(
+        "\
+        crust [https://github.com/Phate6660/crust]\n\
+        For more info on each topic, use `help TOPIC`.\n\
+        builtins:\n\
+        ---------\n",
+    );
+    for builtin in BUILTINS {
+        output.push_str(builtin.name());
+        output.push('\n');
+    }
+    output.push_str("\nshell:\n------\n");
+    for (topic, _) in TOPICS {
+        output.push_str(topic);
+        output.push('\n');
+    }
+    output
+}
+
+/// Returns a list of builtin commands, or the help for a specific one.
+pub fn help(args: &[String]) -> String {
     if args.is_empty() {
-        println!("\
-            crust [https://github.com/Phate6660/crust]\n\
-            For more info on each topic, use `help TOPIC`.\n\
-            builtins:\n\
-            ---------\n\
-            calc\n\
-            cd\n\
-            echo\n\
-            exit\n\
-            help\n\
-            ls\n\
-            pwd\n\
-            spwd\n\n\
-            shell:\n\
-            ------\n\
-            prompt"
-        );
-        return;
+        return list();
     }
     if args.len() > 1 {
-        println!("Please specify only one command.");
-        return;
+        return String::from("Please specify only one command.\n");
+    }
+    let builtin = find(&args[0]).map(|builtin| builtin.help());
+    let topic = TOPICS.iter().find(|(topic, _)| *topic == args[0]).map(|(_, help)| *help);
+    match builtin.or(topic) {
+        Some(help) => format!("{}\n", help),
+        None => list(),
+    }
+}
+
+pub struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn help(&self) -> &'static str {
+        "Returns information about the builtin commands."
     }
-    match args[0].as_str() {
-        "calc" => println!("Supports +, -, /, x for two numbers.\nE.g. 1+1, 4/2, 2x4, 2-1"),
-        "cat" => println!("Support printing file output, and additionally showing line numbers with `-n`."),
-        "cd" => {
-            println!("\
-                Takes a absolute or relative path and changes directory to it.\n\
-                `cd -` will take you to your previous dir."
-            );
-        }
-        "echo" => println!("Takes n amount of arguments and prints them to stdout."),
-        "exit" => println!("Exits the shell with the given exit code."),
-        "help" => println!("Returns information about the builtin commands."),
-        "ls" => println!("Lists the content of a directory."),
-        "pwd" => println!("Prints the working directory."),
-        "prompt" => {
-            println!("\
-                Can be set to a static string, by just setting the string in the PROMPT env variable,\n\
-                or can be set to a dynamic prompt, by including a command to be executed, by delimiting it with %(),\
-                in the prompt string.\n\
-                Variables are also supported, such as %{{U}} for user, %{{C}} for CWD, etc.\n\
-                e.G.: `%(whoami)E@%(hostname)> `. This will, for my case, produce `zeno@aether> `."
-            );
-        }
-        "spwd" => {
-            println!("\
-                It's basically a shortened `pwd`.\n\
-                Instead of a full dir like `/home/valley/Downloads/git`, it'll print something like `/h/v/D/git`."
-            );
-        }
-        _ => {
-            println!("\
-                cRUSTy [https://github.com/Phate6660/crust]\n\
-                builtins:\n\
-                ---------\n\
-                calc\n\
-                cd\n\
-                echo\n\
-                exit\n\
-                help\n\
-                ls\n\
-                pwd\n\
-                prompt"
-            );
-        }
+
+    fn run(&self, _: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(Ok(help(args)), stdout, stderr)
     }
 }
@@ -0,0 +1,175 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::jobs::{set_foreground, wait_foreground, JobState};
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use std::io::Write;
+
+/// Returns the job spec, if one was given.
+fn job_spec<'a>(name: &'static str, args: &'a [String]) -> Result<Option<&'a str>, ShellError> {
+    match args {
+        [] => Ok(None),
+        [spec] => Ok(Some(spec.as_str())),
+        _ => Err(ShellError::TooManyArguments(name)),
+    }
+}
+
+/// Turns the error of looking up a job into the one of the builtin `name`.
+fn job_error(name: &'static str) -> impl FnOnce(String) -> ShellError {
+    move |why| ShellError::Failed(format!("{}: {}", name, why))
+}
+
+/// Lists the jobs and their state.
+pub fn jobs(shell_state: &mut ShellState) -> String {
+    shell_state.jobs.update();
+    shell_state.jobs.list()
+}
+
+/// Continues a job in the foreground and waits for it.
+pub fn fg(shell_state: &mut ShellState, args: &[String], stderr: &mut Output) -> Result<ExitStatus, ShellError> {
+    let spec = job_spec("fg", args)?;
+    let mut job = shell_state.jobs.remove(spec).map_err(job_error("fg"))?;
+    let _ = writeln!(stderr, "{}", job.command);
+    if shell_state.job_control {
+        set_foreground(job.pgid);
+    }
+    job.resume();
+    Ok(wait_foreground(shell_state, job))
+}
+
+/// Continues a stopped job in the background.
+pub fn bg(shell_state: &mut ShellState, args: &[String]) -> Result<String, ShellError> {
+    let spec = job_spec("bg", args)?;
+    let job = shell_state.jobs.get_mut(spec).map_err(job_error("bg"))?;
+    job.resume();
+    Ok(format!("[{}]+ {} &\n", job.id, job.command))
+}
+
+/// Waits for the given job, or for every job if there is none, returning the exit status of the job.
+/// Stops waiting if a job gets stopped.
+pub fn wait(shell_state: &mut ShellState, args: &[String]) -> Result<ExitStatus, ShellError> {
+    let spec = job_spec("wait", args)?;
+    let jobs = match spec {
+        Some(spec) => vec![shell_state.jobs.remove(Some(spec)).map_err(job_error("wait"))?],
+        None => shell_state.jobs.take_all(),
+    };
+    let mut status = ExitStatus::SUCCESS;
+    for mut job in jobs {
+        match job.wait() {
+            // Without a job spec, `wait` always succeeds.
+            JobState::Done(job_status) if spec.is_some() => status = job_status,
+            JobState::Done(_) => (),
+            _ => {
+                shell_state.jobs.add(job);
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// Removes a job from the jobs, without stopping it.
+pub fn disown(shell_state: &mut ShellState, args: &[String]) -> Result<(), ShellError> {
+    let spec = job_spec("disown", args)?;
+    shell_state.jobs.remove(spec).map_err(job_error("disown"))?;
+    Ok(())
+}
+
+/// Prints the error, if there was one, or returns the exit status.
+fn status_or_error(result: Result<ExitStatus, ShellError>, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+    match result {
+        Ok(status) => status,
+        Err(why) => write_output(Err(why), stdout, stderr),
+    }
+}
+
+pub struct Jobs;
+
+impl Builtin for Jobs {
+    fn name(&self) -> &'static str {
+        "jobs"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Lists the jobs started with `&` or stopped with Ctrl-Z, and their state.\n\
+            The current job is marked with `+`, the one before it with `-`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, _: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(Ok(jobs(shell_state)), stdout, stderr)
+    }
+}
+
+pub struct Fg;
+
+impl Builtin for Fg {
+    fn name(&self) -> &'static str {
+        "fg"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Continues a job in the foreground, e.g. `fg %1`.\n\
+            Without a job spec, the current job is used."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        let result = fg(shell_state, args, stderr);
+        status_or_error(result, stdout, stderr)
+    }
+}
+
+pub struct Bg;
+
+impl Builtin for Bg {
+    fn name(&self) -> &'static str {
+        "bg"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Continues a stopped job in the background, e.g. `bg %1`.\n\
+            Without a job spec, the current job is used."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(bg(shell_state, args), stdout, stderr)
+    }
+}
+
+pub struct Wait;
+
+impl Builtin for Wait {
+    fn name(&self) -> &'static str {
+        "wait"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Waits for a job to finish and returns its exit status, e.g. `wait %1`.\n\
+            Without a job spec, waits for every job."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        status_or_error(wait(shell_state, args), stdout, stderr)
+    }
+}
+
+pub struct Disown;
+
+impl Builtin for Disown {
+    fn name(&self) -> &'static str {
+        "disown"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Removes a job from the jobs, without stopping it.\n\
+            Without a job spec, the current job is used."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(disown(shell_state, args).map(|()| String::new()), stdout, stderr)
+    }
+}
@@ -0,0 +1,43 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use crate::variables::is_valid_name;
+
+/// Makes variables local to the function that is running, optionally setting them.
+/// Takes `NAME` or `NAME=value` arguments. The previous values are restored when the function returns.
+pub fn local(shell_state: &mut ShellState, args: &[String]) -> Result<(), ShellError> {
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            return Err(ShellError::invalid_argument("local", arg, "not a valid identifier"));
+        }
+        if !shell_state.variables.make_local(name) {
+            return Err(ShellError::Failed(String::from("local: can only be used in a function")));
+        }
+        if let Some(value) = value {
+            shell_state.variables.set(name, value);
+        }
+    }
+    Ok(())
+}
+
+pub struct Local;
+
+impl Builtin for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn help(&self) -> &'static str {
+        "Makes variables local to the current function, e.g. `local name=value other`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(local(shell_state, args).map(|()| String::new()), stdout, stderr)
+    }
+}
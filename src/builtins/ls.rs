@@ -1,12 +1,17 @@
-use crate::commands::is_piped;
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use std::path::Path;
 
 /// List dir entries. Take the args part of `ShellCommand`.
-pub fn ls(mut args: Vec<String>) -> String {
+/// Relative paths are relative to `cwd`.
+pub fn ls(cwd: &Path, mut args: Vec<String>) -> Result<String, ShellError> {
     let mut output = String::new();
     if args.is_empty() {
         args.push(".".to_string());
     }
-    is_piped(&args, "ls");
     let mut path_idx = 0;
     for (idx, arg) in args.iter().enumerate() {
         if !arg.starts_with("--") || !arg.starts_with('-') {
@@ -14,20 +19,15 @@ pub fn ls(mut args: Vec<String>) -> String {
         }
     }
     let input = &args[path_idx];
-    let path;
-    if std::path::Path::new(input).exists() {
-        path = std::fs::read_dir(input).unwrap();
-    } else {
-        println!("ERROR: '{}' is not a valid file or directory.", input);
-        return String::from("");
-    }
+    let read_error = |why| ShellError::io(format!("ls: {}", input), why);
+    let path = std::fs::read_dir(cwd.join(input)).map_err(read_error)?;
 
     for file in path {
-        let raw_entry = file.unwrap().path();
+        let raw_entry = Path::new(input).join(file.map_err(read_error)?.file_name());
         #[cfg(any(target_os = "android", target_os = "linux"))]
-        let still_raw_entry = raw_entry.to_str().unwrap().replace("./", "");
+        let still_raw_entry = raw_entry.to_string_lossy().replace("./", "");
         #[cfg(target_os = "windows")]
-        let still_raw_entry = raw_entry.to_str().unwrap().replace(".\\", "");
+        let still_raw_entry = raw_entry.to_string_lossy().replace(".\\", "");
         let paths = still_raw_entry.split('\n');
         for line in paths {
             #[cfg(any(target_os = "android", target_os = "linux"))]
@@ -64,5 +64,21 @@ pub fn ls(mut args: Vec<String>) -> String {
             output.push('\n');
         }
     }
-    output
+    Ok(output)
+}
+
+pub struct Ls;
+
+impl Builtin for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn help(&self) -> &'static str {
+        "Lists the content of a directory."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(ls(&shell_state.cwd, args.to_vec()), stdout, stderr)
+    }
 }
@@ -1,7 +1,90 @@
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use std::io::Write;
+
 pub mod calc;
 pub mod cat;
 pub mod cd;
 pub mod echo;
+pub mod env;
+pub mod export;
+pub mod flow;
 pub mod help;
+pub mod jobs;
+pub mod local;
 pub mod ls;
+pub mod pwd;
+pub mod set;
 pub mod spwd;
+pub mod trap;
+pub mod unset;
+
+/// A command that is built into the shell.
+/// To add a builtin, implement this trait and add it to `BUILTINS`,
+/// which is used to run it, to list it in `help` and to complete its name.
+pub trait Builtin: Sync {
+    fn name(&self) -> &'static str;
+    /// The text shown by `help NAME`.
+    fn help(&self) -> &'static str;
+    /// Runs the builtin with the given arguments and streams, returning its exit status.
+    /// In a pipeline, `shell_state` is a copy, so changes to it are lost afterwards.
+    fn run(
+        &self,
+        shell_state: &mut ShellState,
+        args: &[String],
+        stdin: &mut Input,
+        stdout: &mut Output,
+        stderr: &mut Output,
+    ) -> ExitStatus;
+}
+
+/// Every builtin, sorted by name.
+pub static BUILTINS: &[&dyn Builtin] = &[
+    &jobs::Bg,
+    &flow::Break,
+    &calc::Calc,
+    &cat::Cat,
+    &cd::Cd,
+    &flow::Continue,
+    &jobs::Disown,
+    &echo::Echo,
+    &env::Env,
+    &flow::Exit,
+    &export::Export,
+    &jobs::Fg,
+    &help::Help,
+    &jobs::Jobs,
+    &local::Local,
+    &ls::Ls,
+    &pwd::Pwd,
+    &flow::Return,
+    &set::Set,
+    &spwd::Spwd,
+    &trap::Trap,
+    &unset::Unset,
+    &jobs::Wait,
+];
+
+/// Returns the builtin called `name`, if there is one.
+pub fn find(name: &str) -> Option<&'static dyn Builtin> {
+    BUILTINS.iter().copied().find(|builtin| builtin.name() == name)
+}
+
+/// Writes the output of a builtin to its stdout, or the error to its stderr.
+/// Most builtins return a string instead of printing it themselves, to make testing easier.
+pub fn write_output(output: Result<String, ShellError>, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+    match output {
+        Ok(output) => {
+            // The reader may have gone away already, e.g. in `ls | head -1`, which is fine.
+            let _ = stdout.write_all(output.as_bytes());
+            let _ = stdout.flush();
+            ExitStatus::SUCCESS
+        }
+        Err(why) => {
+            let _ = writeln!(stderr, "{}", why);
+            why.status()
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+
+pub struct Pwd;
+
+impl Builtin for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn help(&self) -> &'static str {
+        "Prints the working directory."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, _: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(Ok(format!("{}\n", shell_state.cwd.display())), stdout, stderr)
+    }
+}
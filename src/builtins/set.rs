@@ -0,0 +1,79 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::options::ShellOption;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+
+/// Turns options on with `-o NAME` or their flag, like `-e`, and off with `+o NAME` or `+e`.
+/// Flags can be combined, and an `o` among them takes the next argument as its name, like in `-euo pipefail`.
+/// Without a name, or without any arguments, every option is listed instead.
+pub fn set(shell_state: &mut ShellState, args: &[String]) -> Result<String, ShellError> {
+    if args.is_empty() {
+        return Ok(list(shell_state));
+    }
+    let mut output = String::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let enable = match arg.chars().next() {
+            Some('-') if arg.len() > 1 => true,
+            Some('+') if arg.len() > 1 => false,
+            _ => return Err(ShellError::invalid_argument("set", arg, "invalid option")),
+        };
+        for flag in arg[1..].chars() {
+            let option = match flag {
+                'o' => match args.next() {
+                    Some(name) => match ShellOption::from_name(name) {
+                        Some(option) => option,
+                        None => return Err(ShellError::invalid_argument("set", name, "invalid option name")),
+                    },
+                    None => {
+                        output.push_str(&list(shell_state));
+                        continue;
+                    }
+                },
+                _ => match ShellOption::from_flag(flag) {
+                    Some(option) => option,
+                    None => return Err(ShellError::invalid_argument("set", arg, "invalid option")),
+                },
+            };
+            shell_state.options.set(option, enable);
+        }
+    }
+    Ok(output)
+}
+
+/// Lists every option and whether it is on.
+fn list(shell_state: &ShellState) -> String {
+    let mut output = String::new();
+    for (name, enabled) in shell_state.options.list() {
+        output.push_str(&format!("{:<15} {}\n", name, if enabled { "on" } else { "off" }));
+    }
+    output
+}
+
+pub struct Set;
+
+impl Builtin for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Turns an option on with `set -o NAME` or its flag, like `set -e`, and off with `set +o NAME` or `set +e`.\n\
+            Flags can be combined, like in `set -euo pipefail`. `set -o` lists all options. Options:\n\
+            errexit (-e): exit when a command fails, unless it is checked by `if`, `while`, `&&` or `||`.\n\
+            failglob: patterns that don't match any files are an error.\n\
+            noclobber (-C): `>` doesn't overwrite existing files, but `>|` does.\n\
+            noglob (-f): patterns aren't replaced by the paths they match.\n\
+            nounset (-u): expanding a variable that isn't set is an error.\n\
+            nullglob: patterns that don't match any files are removed.\n\
+            pipefail: a pipeline fails if any command in it fails.\n\
+            xtrace (-x): print every command before it runs, behind `$PS4`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(set(shell_state, args), stdout, stderr)
+    }
+}
@@ -1,27 +1,39 @@
-pub fn print(args: Vec<String>) -> String {
-    let dir = if args.get(1).is_none() || args[1] == "." {
-        let dir = std::env::current_dir().unwrap();
-        let dir = dir.to_string_lossy();
-        dir.to_string()
-    } else {
-        args.get(1).unwrap().to_string()
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+
+pub fn print(shell_state: &ShellState, args: &[String]) -> String {
+    let dir = match args.first().map(String::as_str) {
+        None | Some(".") => shell_state.cwd.to_string_lossy().to_string(),
+        Some(dir) => dir.to_string(),
     };
-    let dir_vec = dir.split('/').collect::<Vec<&str>>();
-    let dir_vec_count = dir_vec.len();
-    let mut n=0;
+    let dir_vec = dir.split('/').skip(1).collect::<Vec<&str>>();
     let mut output = String::new();
-    for segment in dir_vec {
-        if n == 0 {
-            n += 1;
-            continue;
+    if let Some((last, parents)) = dir_vec.split_last() {
+        // Empty segments, like in `/usr//lib`, are left out.
+        for char in parents.iter().filter_map(|segment| segment.chars().next()) {
+            output = output + &format!("/{}", char);
         }
-        if n < dir_vec_count - 1 {
-            let char = segment.chars().collect::<Vec<char>>()[0];
-            output = output + &format!("/{}", char).to_string();
-        } else if n == dir_vec_count - 1 {
-            output = output + &format!("/{}", segment).to_string();
-        }
-        n += 1;
+        output = output + &format!("/{}", last);
     }
     output
 }
+
+pub struct Spwd;
+
+impl Builtin for Spwd {
+    fn name(&self) -> &'static str {
+        "spwd"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            It's basically a shortened `pwd`.\n\
+            Instead of a full dir like `/home/valley/Downloads/git`, it'll print something like `/h/v/D/git`."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(Ok(print(shell_state, args) + "\n"), stdout, stderr)
+    }
+}
@@ -0,0 +1,62 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::signals;
+use crate::streams::{Input, Output};
+use crate::traps::Condition;
+
+/// Sets the command to run when the shell gets one of the given signals, or for `EXIT`, `ERR` or `DEBUG`.
+/// An empty command ignores the signals, and `-` resets them.
+/// Without any arguments, or with `-p`, the traps are listed, and `-l` lists the signals.
+pub fn trap(shell_state: &mut ShellState, args: &[String]) -> Result<String, ShellError> {
+    let args = match args.first().map(String::as_str) {
+        None | Some("-p") => return Ok(shell_state.traps.list()),
+        Some("-l") => {
+            let mut output = String::new();
+            for (name, number) in signals::names() {
+                output.push_str(&format!("{:>2}) SIG{}\n", number, name));
+            }
+            return Ok(output);
+        }
+        Some("--") => &args[1..],
+        Some(_) => args,
+    };
+    let (command, conditions) = match args {
+        [] => return Ok(shell_state.traps.list()),
+        // With only a condition, its trap is reset.
+        [condition] => (None, std::slice::from_ref(condition)),
+        [command, conditions @ ..] if command == "-" => (None, conditions),
+        [command, conditions @ ..] => (Some(command), conditions),
+    };
+    let mut invalid = Vec::new();
+    for condition in conditions {
+        match Condition::parse(condition) {
+            Ok(condition) => shell_state.traps.set(condition, command.cloned()),
+            Err(why) => invalid.push(why),
+        }
+    }
+    ShellError::combine(invalid)?;
+    Ok(String::new())
+}
+
+pub struct Trap;
+
+impl Builtin for Trap {
+    fn name(&self) -> &'static str {
+        "trap"
+    }
+
+    fn help(&self) -> &'static str {
+        "\
+            Runs a command when the shell gets a signal, e.g. `trap 'rm -f $tmp' EXIT INT TERM`.\n\
+            Besides signals, `EXIT` runs when the shell exits, `ERR` after a command failed,\n\
+            and `DEBUG` before every command.\n\
+            An empty command ignores the signals, and `-` resets them.\n\
+            Without arguments, or with `-p`, lists the traps. `-l` lists the signals."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(trap(shell_state, args), stdout, stderr)
+    }
+}
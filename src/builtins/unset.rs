@@ -0,0 +1,33 @@
+use crate::builtins::{write_output, Builtin};
+use crate::commands::ExitStatus;
+use crate::error::ShellError;
+use crate::shared_functions::ShellState;
+use crate::streams::{Input, Output};
+use crate::variables::is_valid_name;
+
+/// Removes the given variables.
+pub fn unset(shell_state: &mut ShellState, args: &[String]) -> Result<(), ShellError> {
+    for name in args {
+        if !is_valid_name(name) {
+            return Err(ShellError::invalid_argument("unset", name, "not a valid identifier"));
+        }
+        shell_state.variables.unset(name);
+    }
+    Ok(())
+}
+
+pub struct Unset;
+
+impl Builtin for Unset {
+    fn name(&self) -> &'static str {
+        "unset"
+    }
+
+    fn help(&self) -> &'static str {
+        "Removes the given variables."
+    }
+
+    fn run(&self, shell_state: &mut ShellState, args: &[String], _: &mut Input, stdout: &mut Output, stderr: &mut Output) -> ExitStatus {
+        write_output(unset(shell_state, args).map(|()| String::new()), stdout, stderr)
+    }
+}
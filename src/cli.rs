@@ -0,0 +1,243 @@
+use crate::error::ShellError;
+use crate::options::ShellOption;
+
+/// Where the commands the shell runs come from.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    /// The string given to `-c`.
+    Command(String),
+    /// A script file.
+    Script(String),
+    /// Stdin, either as a script or typed in at the prompt.
+    Stdin,
+}
+
+/// What to do instead of running the commands, for `--help`, `--version`, `-n` and `--dump-ast`.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Run,
+    Help,
+    Version,
+    /// Only check the commands for syntax errors.
+    Check,
+    /// Only print what the commands were parsed into.
+    DumpAst,
+}
+
+/// The arguments the shell was started with.
+#[derive(Debug)]
+pub struct Cli {
+    pub source: Source,
+    pub action: Action,
+    /// `-i` was given, so the shell is interactive even if stdin is not a terminal.
+    pub interactive: bool,
+    /// `-l` or `--login` was given, or the shell was started with a `-` in front of its name.
+    pub login: bool,
+    /// Don't run the startup file of interactive shells.
+    pub norc: bool,
+    /// Don't run the startup file of login shells.
+    pub noprofile: bool,
+    /// The config file to use instead of the default one.
+    pub config: Option<String>,
+    /// The options like `-e` or `-o pipefail`, as arguments for the `set` builtin.
+    pub options: Vec<String>,
+    /// `$0`, the name given after the command of `-c`, or the script.
+    pub arg0: Option<String>,
+    /// The arguments after the command, script or `-s`, for `$1`, `$2`, ...
+    pub positional: Vec<String>,
+}
+
+pub const USAGE: &str = "\
+Usage: crust [OPTIONS] [SCRIPT [ARGS...]]
+       crust [OPTIONS] -c COMMAND [NAME [ARGS...]]
+       crust [OPTIONS] -s [ARGS...]
+
+Options:
+  -c COMMAND       Run COMMAND, with NAME as $0 and ARGS as $1, $2, ...
+  -i               Run an interactive shell, even if stdin is not a terminal
+  -l, --login      Run as a login shell, which first runs ~/.config/crust/profile
+  -s               Read the commands from stdin, with ARGS as $1, $2, ...
+  -n               Only check the commands for syntax errors
+  --dump-ast       Only print what the commands were parsed into, as JSON
+  --norc           Don't run ~/.config/crust/crustrc in an interactive shell
+  --noprofile      Don't run ~/.config/crust/profile in a login shell
+  --config PATH    Use PATH as the config file
+  --version        Print the version and exit
+  --help           Print this help and exit
+  -C -e -f -u -x   Turn on a shell option, like with `set`, or off with + instead of -
+  -o NAME          Turn on the shell option NAME, or off with +o
+
+Without a SCRIPT or -c, the commands are read from stdin.
+";
+
+fn invalid_option(option: &str) -> ShellError {
+    ShellError::Failed(format!("{}: invalid option", option))
+}
+
+fn missing_argument(option: &str) -> ShellError {
+    ShellError::Failed(format!("{}: option requires an argument", option))
+}
+
+/// Parses the arguments of the shell, without the name it was started with.
+/// Options end at the first argument that isn't one, or at `--` or `-`.
+/// That argument is then the command of `-c`, or the script, unless `-s` was given.
+pub fn parse(arg0: &str, args: &[String]) -> Result<Cli, ShellError> {
+    let mut cli = Cli {
+        source: Source::Stdin,
+        action: Action::Run,
+        interactive: false,
+        login: arg0.starts_with('-'),
+        norc: false,
+        noprofile: false,
+        config: None,
+        options: Vec::new(),
+        arg0: None,
+        positional: Vec::new(),
+    };
+    let mut command = false;
+    let mut read_stdin = false;
+    let mut args = args.iter();
+    let mut operands = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" | "-" => break,
+            "--help" => cli.action = Action::Help,
+            "--version" => cli.action = Action::Version,
+            "--dump-ast" => cli.action = Action::DumpAst,
+            "--login" => cli.login = true,
+            "--norc" => cli.norc = true,
+            "--noprofile" => cli.noprofile = true,
+            "--config" => cli.config = Some(args.next().ok_or_else(|| missing_argument(arg))?.clone()),
+            _ if arg.starts_with("--config=") => cli.config = Some(arg["--config=".len()..].to_string()),
+            _ if arg.starts_with("--") => return Err(invalid_option(arg)),
+            _ if arg.len() > 1 && (arg.starts_with('-') || arg.starts_with('+')) => {
+                let sign = &arg[..1];
+                for flag in arg[1..].chars() {
+                    match flag {
+                        'c' if sign == "-" => command = true,
+                        'i' if sign == "-" => cli.interactive = true,
+                        'l' if sign == "-" => cli.login = true,
+                        's' if sign == "-" => read_stdin = true,
+                        'n' if sign == "-" => cli.action = Action::Check,
+                        'o' => {
+                            let name = args.next().ok_or_else(|| missing_argument(&format!("{}o", sign)))?;
+                            cli.options.extend([format!("{}o", sign), name.clone()]);
+                        }
+                        _ if ShellOption::from_flag(flag).is_some() => cli.options.push(format!("{}{}", sign, flag)),
+                        _ => return Err(invalid_option(&format!("{}{}", sign, flag))),
+                    }
+                }
+            }
+            _ => {
+                operands.push(arg.clone());
+                break;
+            }
+        }
+    }
+    if matches!(cli.action, Action::Help | Action::Version) {
+        return Ok(cli);
+    }
+    operands.extend(args.cloned());
+    let mut operands = operands.into_iter();
+    if command {
+        let input = operands.next().ok_or_else(|| missing_argument("-c"))?;
+        cli.source = Source::Command(input);
+        cli.arg0 = operands.next();
+    } else if !read_stdin {
+        if let Some(script) = operands.next() {
+            cli.arg0 = Some(script.clone());
+            cli.source = Source::Script(script);
+        }
+    }
+    cli.positional = operands.collect();
+    Ok(cli)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Cli, ShellError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse("crust", &args)
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options() {
+        let cli = parse_args(&["-eux", "+o", "pipefail", "-o", "noclobber", "script", "-x"]).unwrap();
+        assert_eq!(cli.options, strings(&["-e", "-u", "-x", "+o", "pipefail", "-o", "noclobber"]));
+        assert_eq!(cli.source, Source::Script(String::from("script")));
+        assert_eq!(cli.arg0.as_deref(), Some("script"));
+        assert_eq!(cli.positional, strings(&["-x"]));
+        assert!(parse_args(&["-q"]).is_err());
+        assert!(parse_args(&["+c", "true"]).is_err());
+        assert!(parse_args(&["-o"]).is_err());
+    }
+
+    #[test]
+    fn long_options() {
+        let cli = parse_args(&["--login", "--norc", "--config=/tmp/crust", "--dump-ast"]).unwrap();
+        assert!(cli.login && cli.norc && !cli.noprofile);
+        assert_eq!(cli.config.as_deref(), Some("/tmp/crust"));
+        assert_eq!(cli.action, Action::DumpAst);
+        assert_eq!(cli.source, Source::Stdin);
+        assert_eq!(parse_args(&["--config", "file"]).unwrap().config.as_deref(), Some("file"));
+        assert_eq!(parse_args(&["--config="]).unwrap().config.as_deref(), Some(""));
+        assert!(parse_args(&["--config"]).is_err());
+        assert!(parse_args(&["--unknown"]).is_err());
+        assert!(parse("-crust", &[]).unwrap().login);
+    }
+
+    #[test]
+    fn command() {
+        let cli = parse_args(&["-ec", "echo $0 $1", "name", "a", "-b"]).unwrap();
+        assert_eq!(cli.source, Source::Command(String::from("echo $0 $1")));
+        assert_eq!(cli.options, strings(&["-e"]));
+        assert_eq!(cli.arg0.as_deref(), Some("name"));
+        assert_eq!(cli.positional, strings(&["a", "-b"]));
+        let cli = parse_args(&["-c", "true"]).unwrap();
+        assert_eq!(cli.arg0, None);
+        assert!(cli.positional.is_empty());
+        assert!(parse_args(&["-c"]).is_err());
+    }
+
+    #[test]
+    fn stdin() {
+        let cli = parse_args(&["-s", "a", "b"]).unwrap();
+        assert_eq!(cli.source, Source::Stdin);
+        assert_eq!(cli.arg0, None);
+        assert_eq!(cli.positional, strings(&["a", "b"]));
+        let cli = parse_args(&["-i", "-n"]).unwrap();
+        assert!(cli.interactive);
+        assert_eq!(cli.action, Action::Check);
+        assert_eq!(cli.source, Source::Stdin);
+    }
+
+    #[test]
+    fn end_of_options() {
+        let cli = parse_args(&["-e", "--", "-x", "a"]).unwrap();
+        assert_eq!(cli.options, strings(&["-e"]));
+        assert_eq!(cli.source, Source::Script(String::from("-x")));
+        assert_eq!(cli.positional, strings(&["a"]));
+        let cli = parse_args(&["-", "-script"]).unwrap();
+        assert!(cli.options.is_empty());
+        assert_eq!(cli.source, Source::Script(String::from("-script")));
+        let cli = parse_args(&["-s", "--", "-e"]).unwrap();
+        assert_eq!(cli.source, Source::Stdin);
+        assert!(cli.options.is_empty());
+        assert_eq!(cli.positional, strings(&["-e"]));
+        let cli = parse_args(&["-c", "--", "true", "-e"]).unwrap();
+        assert_eq!(cli.source, Source::Command(String::from("true")));
+        assert_eq!(cli.arg0.as_deref(), Some("-e"));
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert_eq!(parse_args(&["--help", "-c"]).unwrap().action, Action::Help);
+        assert_eq!(parse_args(&["--version"]).unwrap().action, Action::Version);
+    }
+}
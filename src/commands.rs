@@ -1,35 +1,74 @@
-use crate::builtins::{calc::calc, cat::cat, cd::cd, echo::echo, help::help, ls::ls, spwd::print};
-use crate::shared_functions::lex_tokenized_input;
+use crate::builtins::find as find_builtin;
+use crate::error::ShellError;
+use crate::expansion::{expand_word, expand_words};
+use crate::executor::{call_function, run_compound};
+use crate::jobs::{join_group, prepare_child, wait_foreground, Job, Process};
+use crate::lexer::RedirectOp;
+use crate::options::ShellOption;
+use crate::parser::{Command as ParsedCommand, CompoundCommand, Pipeline, Redirect, SimpleCommand};
+use crate::signals::restore_defaults;
+use crate::streams::{Input, Output, Redirection, Streams};
 use crate::ShellState;
-use sflib::ensure_directory;
-use std::io::{Read, Write};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::fs::File;
+use std::process::{Child, Command};
 
-#[derive(Debug, Clone)]
-pub enum Redirection {
-    Overwrite,
-    Append,
-    NoOp,
+/// The exit status of a command, where `0` means success.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ExitStatus {
+    pub code: i32,
+}
+
+impl ExitStatus {
+    pub const SUCCESS: ExitStatus = ExitStatus { code: 0 };
+    pub const FAILURE: ExitStatus = ExitStatus { code: 1 };
+    /// The status used when a command could not be found.
+    pub const NOT_FOUND: ExitStatus = ExitStatus { code: 127 };
+
+    pub fn from_code(code: i32) -> ExitStatus {
+        ExitStatus { code }
+    }
+
+    pub fn success(self) -> bool {
+        self.code == 0
+    }
+}
+
+/// Converts the status of an external command.
+/// Commands killed by a signal get `128 + signal`, like in other shells.
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> ExitStatus {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return ExitStatus::from_code(128 + signal);
+            }
+        }
+        ExitStatus::from_code(status.code().unwrap_or(1))
+    }
 }
 
 /// This struct is used to construct a shellcommand,
 /// be it a builtin or external command.
 /// The `name` String holds the actual command name, like `echo` or `cargo`.
-/// The `args` vector hold all arguments. This includes the pipe char,
-/// which is later used to detect and construct a pipe.
+/// The `args` vector hold all arguments.
+/// The `redirections` vector holds all redirections, in the order they were given.
+/// The `assignments` vector holds the variables set in front of the command,
+/// like `FOO=bar` in `FOO=bar cargo build`.
 #[derive(Debug, Clone)]
 pub struct ShellCommand {
     pub name: String,
     pub args: Vec<String>,
-    pub redirection: Redirection,
+    pub redirections: Vec<Redirection>,
+    pub assignments: Vec<(String, String)>,
 }
 
-pub fn return_shellcommand(name: String, args: Vec<String>, redirection: Redirection) -> ShellCommand {
+pub fn return_shellcommand(name: String, args: Vec<String>, redirections: Vec<Redirection>) -> ShellCommand {
     ShellCommand {
         name,
         args,
-        redirection,
+        redirections,
+        assignments: Vec::new(),
     }
 }
 
@@ -41,116 +80,196 @@ impl std::fmt::Display for ShellCommand {
 }
 
 impl ShellCommand {
-    /// Constructs a new `ShellCommand` and returns it.
-    /// Takes the input given by the user, unprocessed.
-    pub fn new(input: &str) -> ShellCommand {
-        fn get_redirection_type(input: &str) -> Redirection {
-            if input.contains(">>") {
-                Redirection::Append
-            } else if input.contains('>') {
-                Redirection::Overwrite
-            } else {
-                Redirection::NoOp
-            }
+    /// Constructs a new `ShellCommand` from a parsed `SimpleCommand`,
+    /// expanding all of its words.
+    pub fn expand(shell_state: &mut ShellState, command: &SimpleCommand) -> Result<ShellCommand, String> {
+        let mut words = expand_words(shell_state, &command.words)?.into_iter();
+        let redirections = expand_redirects(shell_state, &command.redirects)?;
+        let mut assignments = Vec::new();
+        for assignment in &command.assignments {
+            assignments.push((assignment.name.clone(), expand_word(shell_state, &assignment.value)?));
         }
-        let lexed_vec = lex_tokenized_input(input);
-        ShellCommand {
-            name: lexed_vec[0].clone(),
-            args: lexed_vec[1..].to_vec(),
-            redirection: get_redirection_type(input),
+        let command = ShellCommand {
+            name: words.next().unwrap_or_default(),
+            args: words.collect(),
+            redirections,
+            assignments,
+        };
+        if shell_state.options.is_set(ShellOption::Xtrace) {
+            command.trace(shell_state);
         }
+        Ok(command)
+    }
+
+    /// Prints the command to stderr for `xtrace`, behind the value of `$PS4`, or `+ ` if it isn't set.
+    /// Words with special characters in them are quoted, so they can be told apart.
+    fn trace(&self, shell_state: &ShellState) {
+        let mut words = Vec::new();
+        for (name, value) in &self.assignments {
+            words.push(format!("{}={}", name, quote(value)));
+        }
+        if !self.name.is_empty() {
+            words.push(quote(&self.name));
+            words.extend(self.args.iter().map(|arg| quote(arg)));
+        }
+        eprintln!("{}{}", shell_state.variables.get("PS4").unwrap_or("+ "), words.join(" "));
+    }
+
+    /// The name and arguments of the command, separated by spaces.
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.name).chain(&self.args).cloned().collect::<Vec<_>>().join(" ")
     }
 
     /// Takes a `ShellCommand`, figures out what to do given the name,
-    /// then executes it.
-    /// All builtins have to be listed here and point to their given function.
-    /// It is prefered that they return a string, which gets printed here,
-    /// and not by the actual function, to make testing easier.
-    pub fn run(shell_state: &mut ShellState, command: ShellCommand) {
-        // check for piping first, because otherwise redirecting builtins
-        // would match the builtin and piping
-        if command.args.contains(&String::from("|"))
-            || command.args.contains(&String::from(">>"))
-            || command.args.contains(&String::from(">"))
-        {
-            println!("{}", piped_cmd(&PipedShellCommand::from(&command)));
-        } else {
-            match command.name.as_str() {
-                "calc" => println!("{}", calc(&command.args)),
-                "cat" => println!("{}", cat(&command.args)),
-                "cd" => cd(shell_state, &command),
-                "echo" => println!("{}", echo(&command.args)),
-                "help" => help(&command.args),
-                "ls" => print!("{}", ls(command.args)),
-                "pwd" => println!("{}", std::env::current_dir().unwrap().display()),
-                "spwd" => println!("{}", print(command.args)),
-                _ => {
-                    cmd(&command);
-                }
+    /// then executes it, returning its exit status.
+    /// Functions are looked up first, then the builtins in `builtins::BUILTINS`,
+    /// and everything else is ran as an external command.
+    /// The redirections of the command are applied on top of `streams`.
+    pub fn run(shell_state: &mut ShellState, command: ShellCommand, mut streams: Streams) -> ExitStatus {
+        if let Err(why) = streams.redirect_all(&command.redirections, &shell_state.cwd) {
+            return why.report();
+        }
+        // Only assignments or redirections were given, e.g. `FOO=bar` or `> file`.
+        if command.name.is_empty() {
+            for (name, value) in &command.assignments {
+                shell_state.variables.set(name, value);
             }
+            return ExitStatus::SUCCESS;
         }
+        let function = shell_state.functions.get(&command.name).cloned();
+        let builtin = find_builtin(&command.name);
+        if function.is_none() && builtin.is_none() {
+            return cmd(shell_state, &command, streams);
+        }
+        // The assignments in front of a function or builtin only last until it is done.
+        let saved = shell_state.variables.assign_temporarily(&command.assignments);
+        let status = match (function, builtin) {
+            (Some(function), _) => call_function(shell_state, &function, command.args, streams),
+            (None, Some(builtin)) => builtin.run(
+                shell_state,
+                &command.args,
+                &mut streams.stdin,
+                &mut streams.stdout,
+                &mut streams.stderr,
+            ),
+            (None, None) => unreachable!(),
+        };
+        shell_state.variables.restore(saved);
+        status
+    }
+}
+
+/// Puts `word` in single quotes if it is empty or has characters in it that the shell would treat specially.
+fn quote(word: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./:=@%+,".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Expands the targets of parsed redirections.
+pub fn expand_redirects(shell_state: &mut ShellState, redirects: &[Redirect]) -> Result<Vec<Redirection>, String> {
+    let mut redirections = Vec::new();
+    for redirect in redirects {
+        let op = match redirect.op {
+            // Without `noclobber`, `>` overwrites files just like `>|` does.
+            RedirectOp::Overwrite if !shell_state.options.is_set(ShellOption::Noclobber) => RedirectOp::Clobber,
+            op => op,
+        };
+        redirections.push(Redirection {
+            fd: redirect.fd.unwrap_or_else(|| redirect.op.default_fd()),
+            op,
+            target: expand_word(shell_state, &redirect.target)?,
+        });
     }
+    Ok(redirections)
+}
+
+/// A command in a pipeline.
+#[derive(Debug)]
+pub enum PipedCommand {
+    Simple(ShellCommand),
+    /// A compound command like a loop, whose words are expanded while it runs.
+    Compound(CompoundCommand, Vec<Redirection>),
 }
 
 /// This struct is a vector, containing all commands and their arguments
-/// in a pipeline. Every command is represented by a `ShellCommand`.
+/// in a pipeline. Every command is represented by a `PipedCommand`.
 #[derive(Debug)]
 pub struct PipedShellCommand {
-    pub commands: Vec<ShellCommand>,
+    pub commands: Vec<PipedCommand>,
+    /// The pipeline as it was written, e.g. for `jobs`.
+    pub text: String,
 }
 
 impl PipedShellCommand {
-    /// Constructs a `PipedShellCommand` from a given `ShellCommand`.
-    /// Takes a `ShellCommand` containing a pipe.
-    pub fn from(input: &ShellCommand) -> PipedShellCommand {
-        fn get_redirection_type(input: &ShellCommand) -> Redirection {
-            if input.args.contains(&String::from(">>")) {
-                Redirection::Append
-            } else if input.args.contains(&String::from(">")) {
-                Redirection::Overwrite
-            } else {
-                Redirection::NoOp
-            }
-        }
-        let parts = input.args.split(|arg| {
-            arg == &String::from("|")  ||
-            // Check for appending first because `>` would match both.
-            arg == &String::from(">>") ||
-            arg == &String::from(">")
-        });
-        let mut commands: Vec<ShellCommand> = Vec::new();
-        for (idx, part) in parts.enumerate() {
-            if idx == 0 {
-                let command = ShellCommand {
-                    name: input.name.clone(),
-                    args: part[0..].to_vec(),
-                    redirection: get_redirection_type(input),
-                };
-                commands.push(command);
-            } else {
-                let command = ShellCommand {
-                    name: part[0].clone(),
-                    args: part[1..].to_vec(),
-                    redirection: get_redirection_type(input),
-                };
-                commands.push(command);
-            }
+    /// Constructs a `PipedShellCommand` from a parsed `Pipeline`,
+    /// expanding the words of every simple command.
+    pub fn expand(shell_state: &mut ShellState, pipeline: &Pipeline) -> Result<PipedShellCommand, String> {
+        let mut commands = Vec::new();
+        for command in &pipeline.commands {
+            commands.push(match command {
+                ParsedCommand::Simple(command) => PipedCommand::Simple(ShellCommand::expand(shell_state, command)?),
+                ParsedCommand::Compound(command, redirects) => {
+                    PipedCommand::Compound(command.clone(), expand_redirects(shell_state, redirects)?)
+                }
+                // Every command of a pipeline runs in its own copy of the shell,
+                // so defining a function there has no effect.
+                ParsedCommand::Function(_) => PipedCommand::Simple(return_shellcommand(String::new(), Vec::new(), Vec::new())),
+            });
         }
-        PipedShellCommand { commands }
+        Ok(PipedShellCommand {
+            commands,
+            text: pipeline.text.clone(),
+        })
     }
 }
 
-/// Helper function to a command, optionally with args.
-pub fn cmd(command: &ShellCommand) {
-    let child = Command::new(&command.name)
+/// Spawns an external command with the given streams, in the shell's working directory.
+/// The command gets all exported variables as its environment,
+/// plus the ones assigned in front of it.
+/// With job control, it joins the process group `pgid`, or starts a new one if that is `None`.
+fn spawn(
+    shell_state: &ShellState,
+    command: &ShellCommand,
+    streams: Streams,
+    pgid: &mut Option<u32>,
+    foreground: bool,
+) -> std::io::Result<Child> {
+    let mut child = Command::new(&command.name);
+    child
         .args(&command.args)
-        .spawn();
-    if let Ok(..) = child {
-        let output = child.unwrap().wait_with_output().unwrap().stdout;
-        let usable_output = std::str::from_utf8(&output).unwrap();
-        println!("{}", usable_output);
-    } else {
-        println!("Sorry, '{}' was not found!", command.name);
+        .current_dir(&shell_state.cwd)
+        .env_clear()
+        .envs(shell_state.variables.exported())
+        .envs(command.assignments.iter().map(|(name, value)| (name, value)));
+    streams.apply(&mut child);
+    if shell_state.job_control {
+        prepare_child(&mut child, *pgid, foreground);
+    }
+    // Only after taking the terminal, which would stop the command if SIGTTOU wasn't ignored.
+    restore_defaults(&mut child);
+    let child = child.spawn()?;
+    if shell_state.job_control {
+        *pgid = Some(join_group(&child, *pgid));
+    }
+    Ok(child)
+}
+
+/// Helper function to run a command, optionally with args.
+/// Unless they were redirected, the command inherits the shell's stdin, stdout
+/// and stderr, so interactive programs like `vim` or `less` work as expected.
+/// With job control, the command can be stopped with Ctrl-Z, which turns it into a job.
+pub fn cmd(shell_state: &mut ShellState, command: &ShellCommand, streams: Streams) -> ExitStatus {
+    let mut pgid = None;
+    match spawn(shell_state, command, streams, &mut pgid, true) {
+        Ok(child) => {
+            let job = Job::new(command.command_line(), pgid, vec![Process::External { child, status: None }]);
+            wait_foreground(shell_state, job)
+        }
+        Err(why) => ShellError::spawn(&command.name, why).report(),
     }
 }
 
@@ -158,138 +277,119 @@ pub fn cmd_with_output(command: &ShellCommand) -> String {
     let child = Command::new(&command.name)
         .args(&command.args)
         .output();
-    if let Ok(..) = child {
-        std::string::String::from_utf8_lossy(&child.unwrap().stdout).to_string()
+    if let Ok(child) = child {
+        std::string::String::from_utf8_lossy(&child.stdout).to_string()
     } else {
-        "Sorry, '{}' was not found!".to_string()
+        ShellError::CommandNotFound(command.name.clone()).to_string()
     }
 }
 
-/// This is a function for checking if the command is piped.
-/// Used to remove a lot of duplicate code.
-pub fn is_piped(args: &[String], cmd: &str) {
-    fn run_pipe(cmd: &str, args: &[String], redirection: Redirection) {
-        let command = return_shellcommand(cmd.to_string(), args.to_vec(), redirection);
-        let pipe = PipedShellCommand::from(&command);
-        piped_cmd(&pipe);
-    }
-    if args.contains(&"|".to_string()) {
-        run_pipe(cmd, args, Redirection::NoOp);
-    } else if args.contains(&">>".to_string()) {
-        run_pipe(cmd, args, Redirection::Append);
-    } else if args.contains(&">".to_string()) {
-        run_pipe(cmd, args, Redirection::Overwrite);
-    } else {
-        // Do nothing.
-    }
+/// Duplicates the file descriptors above 2 of a `Streams`.
+fn clone_files(files: &[(u32, File)]) -> std::io::Result<Vec<(u32, File)>> {
+    files.iter().map(|(fd, file)| Ok((*fd, file.try_clone()?))).collect()
 }
 
-/// Returns a `Child` of a command wrapped in a `Result`.
-fn return_child(cmd: &str, args: &[String]) -> Result<std::process::Child, ()> {
-    Command::new(cmd)
-        .args(args)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .or(Err(()))
-}
-
-/// Takes a `PipedShellCommand`, iterating over all `ShellCommand` structs
-/// contained by it, checking if it is the first or the last in the pipeline,
-/// and taking the appropriate meassurements to pipe stdout.
-pub fn piped_cmd(pipe: &PipedShellCommand) -> String {
-    let mut output_prev = String::new();
-    match pipe.commands[0].name.as_str() {
-        "cat" => output_prev = cat(&pipe.commands[0].args.clone()),
-        "echo" => output_prev = echo(&pipe.commands[0].args.clone()),
-        "calc" => output_prev = calc(&pipe.commands[0].args.clone()),
-        "ls" => output_prev = ls(pipe.commands[0].args.clone()),
-        _ => {
-            let child = return_child(&pipe.commands[0].name.clone(), &pipe.commands[0].args);
-            if child.is_err() {
-                println!("{} failed", pipe.commands[0].name.clone());
-            }
-            child.unwrap().stdout.unwrap().read_to_string(&mut output_prev).unwrap();
-        }
-    }
+/// Takes a `PipedShellCommand`, starting all commands
+/// contained by it at the same time, with the stdout of every command
+/// connected to the stdin of the next one through a pipe.
+/// Builtins and compound commands are ran on their own thread, writing to the pipe,
+/// with their own copy of the `ShellState`. With job control, the external commands
+/// share a new process group, which gets the terminal if the pipeline runs in the `foreground`.
+/// The first command reads from the stdin of `streams`, and the last one writes to its stdout,
+/// unless they were redirected. Returns the job, which still has to be waited for.
+pub fn start_pipeline(shell_state: &ShellState, pipe: &PipedShellCommand, streams: Streams, foreground: bool) -> Job {
+    let mut processes = Vec::new();
+    let mut pgid = None;
+    let Streams {
+        stdin: mut next_stdin,
+        stdout,
+        stderr,
+        extra,
+    } = streams;
+    let mut stdout = Some(stdout);
     for (idx, command) in pipe.commands.iter().enumerate() {
-        if idx == 0 {
-            continue;
-        }
-        if idx == pipe.commands.len() - 1 {
-            break;
-        }
-        match command.name.as_str() {
-            "cat" => output_prev = cat(&command.args.clone()),
-            "echo" => output_prev = echo(&command.args.clone()),
-            "calc" => output_prev = calc(&command.args.clone()),
-            "ls" => output_prev = ls(command.args.clone()),
-            _ => {
-                let child = return_child(&command.name.clone(), &command.args);
-                match child {
-                    Ok(mut child) => {
-                        child.stdin.take().unwrap().write_all(output_prev.as_bytes()).unwrap();
-                        output_prev = "".to_string();
-                        child.stdout.unwrap().read_to_string(&mut output_prev).unwrap();
-                    }
-                    Err(_) => println!("{} failed", command.name.clone()),
+        let stdin = std::mem::replace(&mut next_stdin, Input::Closed);
+        let output = if idx == pipe.commands.len() - 1 {
+            stdout.take().unwrap()
+        } else {
+            match std::io::pipe() {
+                Ok((reader, writer)) => {
+                    next_stdin = Input::Pipe(reader);
+                    Output::Pipe(writer)
+                }
+                Err(why) => {
+                    ShellError::io("could not create a pipe", why).report();
+                    processes.push(Process::Done(ExitStatus::FAILURE));
+                    break;
                 }
             }
-        }
-    }
-    let file_string = &pipe.commands[pipe.commands.len() - 1].name;
-    if file_string.contains('/') {
-        let file_vec: Vec<&str> = file_string.split('/').collect();
-        let mut parent_dir = String::new();
-        for (id, chunk) in file_vec.iter().enumerate() {
-            if id == file_vec.len() - 1 {
-                break;
+        };
+        let mut streams = match (stderr.try_clone(), clone_files(&extra)) {
+            (Ok(stderr), Ok(extra)) => Streams {
+                stdin,
+                stdout: output,
+                stderr,
+                extra,
+            },
+            (Err(why), _) | (_, Err(why)) => {
+                ShellError::io("could not duplicate the streams", why).report();
+                processes.push(Process::Done(ExitStatus::FAILURE));
+                continue;
             }
-            let part = format!("{}/", chunk);
-            parent_dir.push_str(&part);
-        }
-        ensure_directory(&parent_dir, true).unwrap();
-    }
-    let file_path = &Path::new(file_string);
-    match pipe.commands[pipe.commands.len() - 1].redirection {
-        Redirection::Overwrite => {
-            let mut file = std::fs::File::create(file_path).unwrap();
-            file.write_all(output_prev.as_bytes()).unwrap();
-            return String::new();
-        }
-        Redirection::Append => {
-            let mut file = std::fs::OpenOptions::new()
-                .write(true)
-                .append(true)
-                .create(true)
-                .open(file_path)
-                .unwrap();
-            writeln!(file, "{}", output_prev).unwrap();
-            return String::new();
+        };
+        let redirections = match command {
+            PipedCommand::Simple(command) => &command.redirections,
+            PipedCommand::Compound(_, redirections) => redirections,
+        };
+        if let Err(why) = streams.redirect_all(redirections, &shell_state.cwd) {
+            processes.push(Process::Done(why.report()));
+            continue;
         }
-        Redirection::NoOp => (),
-    }
-    match pipe.commands[pipe.commands.len() - 1].name.as_str() {
-        "cat" => cat(&pipe.commands[pipe.commands.len() - 1].args.clone()),
-        "echo" => echo(&pipe.commands[pipe.commands.len() - 1].args.clone()),
-        "calc" => calc(&pipe.commands[pipe.commands.len() - 1].args.clone()),
-        "ls" => ls(pipe.commands[pipe.commands.len() - 1].args.clone()),
-        _ => {
-            let child = return_child(
-                &pipe.commands[pipe.commands.len() - 1].name.clone(),
-                &pipe.commands[pipe.commands.len() - 1].args,
-            );
-            match child {
-                Ok(mut child) => {
-                    child.stdin.take().unwrap().write_all(output_prev.as_bytes()).unwrap();
-                    let mut output = String::new();
-                    match child.stdout.take().unwrap().read_to_string(&mut output) {
-                        Err(why) => format!("ERROR: could not read cmd2 stdout: {}", why),
-                        Ok(_) => output,
-                    }
-                }
-                Err(_) => pipe.commands[pipe.commands.len() - 1].name.clone(),
+        let command = match command {
+            PipedCommand::Simple(command) => command,
+            PipedCommand::Compound(command, _) => {
+                let command = command.clone();
+                let mut shell_state = shell_state.subshell();
+                processes.push(Process::Thread(std::thread::spawn(move || {
+                    run_compound(&mut shell_state, &command, &streams)
+                })));
+                continue;
             }
+        };
+        if let Some(function) = shell_state.functions.get(&command.name).cloned() {
+            let args = command.args.clone();
+            let mut shell_state = shell_state.subshell();
+            shell_state.variables.assign_temporarily(&command.assignments);
+            processes.push(Process::Thread(std::thread::spawn(move || {
+                call_function(&mut shell_state, &function, args, streams)
+            })));
+            continue;
         }
+        let process = match find_builtin(&command.name) {
+            _ if command.name.is_empty() => Process::Done(ExitStatus::SUCCESS),
+            Some(builtin) => {
+                let args = command.args.clone();
+                let mut shell_state = shell_state.subshell();
+                shell_state.variables.assign_temporarily(&command.assignments);
+                Process::Thread(std::thread::spawn(move || {
+                    let Streams {
+                        stdin, stdout, stderr, ..
+                    } = &mut streams;
+                    builtin.run(&mut shell_state, &args, stdin, stdout, stderr)
+                }))
+            }
+            None => match spawn(shell_state, command, streams, &mut pgid, foreground) {
+                Ok(child) => Process::External { child, status: None },
+                Err(why) => Process::Done(ShellError::spawn(&command.name, why).report()),
+            },
+        };
+        processes.push(process);
     }
+    Job::new(pipe.text.clone(), pgid, processes).with_pipefail(shell_state.options.is_set(ShellOption::Pipefail))
+}
+
+/// Runs a `PipedShellCommand` in the foreground, returning the exit status of the last command.
+pub fn piped_cmd(shell_state: &mut ShellState, pipe: &PipedShellCommand, streams: Streams) -> ExitStatus {
+    let job = start_pipeline(shell_state, pipe, streams, true);
+    wait_foreground(shell_state, job)
 }
@@ -0,0 +1,53 @@
+use crate::builtins::BUILTINS;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Completes the names of builtins for the first word of a command,
+/// and file names everywhere else.
+#[derive(Default)]
+pub struct ShellHelper {
+    filenames: FilenameCompleter,
+}
+
+/// Checks if the word that ends at the end of `before` is the name of a command,
+/// because it's the first word, or comes after a `|`, `;`, `&` or `(`.
+fn is_command_name(before: &str) -> bool {
+    let start = before.rfind(char::is_whitespace).map_or(0, |idx| idx + 1);
+    let word = &before[start..];
+    let previous = before[..start].trim_end();
+    !word.contains('/') && (previous.is_empty() || previous.ends_with(&['|', ';', '&', '('][..]))
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        if !is_command_name(before) {
+            return self.filenames.complete(line, pos, ctx);
+        }
+        let start = before.rfind(char::is_whitespace).map_or(0, |idx| idx + 1);
+        let candidates = BUILTINS
+            .iter()
+            .filter(|builtin| builtin.name().starts_with(&before[start..]))
+            .map(|builtin| Pair {
+                display: builtin.name().to_string(),
+                replacement: format!("{} ", builtin.name()),
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
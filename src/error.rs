@@ -0,0 +1,130 @@
+use crate::commands::ExitStatus;
+use std::fmt;
+
+/// An error from running a command. It is printed, and the shell goes on with the next command.
+#[derive(Debug)]
+pub enum ShellError {
+    /// A builtin got an argument it can't use, e.g. `calc abc` or `cat -l x file`.
+    InvalidArgument {
+        command: &'static str,
+        argument: String,
+        reason: String,
+    },
+    /// A builtin is missing an argument it needs, e.g. the file in `cat -n`.
+    MissingArgument { command: &'static str, argument: &'static str },
+    /// A builtin got more arguments than it takes.
+    TooManyArguments(&'static str),
+    /// A file or directory could not be used, e.g. because it doesn't exist.
+    Io { context: String, error: std::io::Error },
+    /// There is no function, builtin or external command with this name.
+    CommandNotFound(String),
+    /// An external command was found, but could not be started.
+    CannotExecute { command: String, error: std::io::Error },
+    /// Expanding the words of a command failed, e.g. because of `${name?}`.
+    Expansion(String),
+    /// A redirection could not be applied.
+    Redirection(String),
+    /// Any other error, with a message that starts with the name of the builtin.
+    Failed(String),
+    /// Several errors of a builtin that went on after the first one, like `export 1a 2b`.
+    Multiple(Vec<ShellError>),
+}
+
+impl ShellError {
+    pub fn invalid_argument(command: &'static str, argument: &str, reason: impl Into<String>) -> ShellError {
+        ShellError::InvalidArgument {
+            command,
+            argument: argument.to_string(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn io(context: impl Into<String>, error: std::io::Error) -> ShellError {
+        ShellError::Io {
+            context: context.into(),
+            error,
+        }
+    }
+
+    /// The error for an external command that could not be started.
+    pub fn spawn(command: &str, error: std::io::Error) -> ShellError {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            ShellError::CommandNotFound(command.to_string())
+        } else {
+            ShellError::CannotExecute {
+                command: command.to_string(),
+                error,
+            }
+        }
+    }
+
+    /// Returns all errors as one, or `Ok` if there are none.
+    pub fn combine(mut errors: Vec<ShellError>) -> Result<(), ShellError> {
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(ShellError::Multiple(errors)),
+        }
+    }
+
+    /// The exit status of the command that failed.
+    pub fn status(&self) -> ExitStatus {
+        match self {
+            ShellError::CommandNotFound(_) => ExitStatus::NOT_FOUND,
+            ShellError::CannotExecute { .. } => ExitStatus::from_code(126),
+            ShellError::Multiple(errors) => errors.last().map_or(ExitStatus::FAILURE, ShellError::status),
+            _ => ExitStatus::FAILURE,
+        }
+    }
+
+    /// Prints the error as coming from the shell itself, returning the exit status for it.
+    pub fn report(&self) -> ExitStatus {
+        match self {
+            ShellError::CommandNotFound(_) => eprintln!("{}", self),
+            _ => eprintln!("crust: {}", self),
+        }
+        self.status()
+    }
+}
+
+/// Formats an I/O error without the `(os error 2)` at the end.
+fn io_message(error: &std::io::Error) -> String {
+    let message = error.to_string();
+    match message.find(" (os error") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellError::InvalidArgument {
+                command,
+                argument,
+                reason,
+            } => write!(f, "{}: '{}': {}", command, argument, reason),
+            ShellError::MissingArgument { command, argument } => write!(f, "{}: missing {}", command, argument),
+            ShellError::TooManyArguments(command) => write!(f, "{}: too many arguments", command),
+            ShellError::Io { context, error } => write!(f, "{}: {}", context, io_message(error)),
+            ShellError::CommandNotFound(command) => write!(f, "Sorry, '{}' was not found!", command),
+            ShellError::CannotExecute { command, error } => write!(f, "{}: {}", command, io_message(error)),
+            ShellError::Expansion(message) | ShellError::Redirection(message) | ShellError::Failed(message) => {
+                write!(f, "{}", message)
+            }
+            ShellError::Multiple(errors) => {
+                let messages = errors.iter().map(ShellError::to_string).collect::<Vec<_>>();
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShellError {}
+
+/// Lets builtins use `?` on functions that return a message.
+impl From<String> for ShellError {
+    fn from(message: String) -> ShellError {
+        ShellError::Failed(message)
+    }
+}
@@ -0,0 +1,388 @@
+use crate::commands::{expand_redirects, piped_cmd, start_pipeline, ExitStatus, PipedShellCommand, ShellCommand};
+use crate::error::ShellError;
+use crate::expansion::{expand_pattern, expand_word, expand_words};
+use crate::glob;
+use crate::jobs::{Job, Process};
+use crate::options::ShellOption;
+use crate::parser::{AndOrList, Command, CompoundCommand, Connector, FunctionDefinition, Pipeline, Program};
+use crate::shared_functions::ShellState;
+use crate::signals;
+use crate::traps::{run_pending, run_trap, Condition};
+use crate::streams::{Input, Output, Streams};
+use std::io::Read;
+use std::sync::Arc;
+
+/// A pending `break` or `continue`, with the number of loops it still has to go through,
+/// a pending `return` from a function, or a pending `exit` with its exit code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlFlow {
+    Break(usize),
+    Continue(usize),
+    Return,
+    Exit(i32),
+}
+
+/// The exit status of a command that was stopped with Ctrl-C.
+const INTERRUPTED: ExitStatus = ExitStatus { code: 130 };
+
+/// Reports an error from expanding the words of a command.
+fn expansion_failed(why: &str) -> ExitStatus {
+    ShellError::Expansion(why.to_string()).report()
+}
+
+/// Runs a single pipeline and records its exit status on the `ShellState`.
+/// If expanding its words fails, the error is printed and nothing is run.
+/// The exit status of a pipeline with `!` in front of it is inverted, and like in the condition
+/// of an `if`, commands that fail in it don't run the `ERR` trap or exit with `errexit`.
+pub fn run_pipeline(shell_state: &mut ShellState, pipeline: &Pipeline, streams: &Streams) -> ExitStatus {
+    run_trap(shell_state, Condition::Debug);
+    let streams = match streams.try_clone() {
+        Ok(streams) => streams,
+        Err(why) => return ShellError::io("could not duplicate the streams", why).report(),
+    };
+    if pipeline.negated {
+        shell_state.condition_depth += 1;
+    }
+    let mut status = match pipeline.commands.as_slice() {
+        [Command::Simple(command)] => match ShellCommand::expand(shell_state, command) {
+            Ok(command) => ShellCommand::run(shell_state, command, streams),
+            Err(why) => expansion_failed(&why),
+        },
+        [Command::Function(definition)] => {
+            shell_state.functions.insert(definition.name.clone(), Arc::new(definition.clone()));
+            ExitStatus::SUCCESS
+        }
+        [Command::Compound(command, redirects)] => {
+            let mut streams = streams;
+            match expand_redirects(shell_state, redirects) {
+                Ok(redirections) => match streams.redirect_all(&redirections, &shell_state.cwd) {
+                    Ok(()) => run_compound(shell_state, command, &streams),
+                    Err(why) => why.report(),
+                },
+                Err(why) => expansion_failed(&why),
+            }
+        }
+        _ => match PipedShellCommand::expand(shell_state, pipeline) {
+            Ok(pipe) => piped_cmd(shell_state, &pipe, streams),
+            Err(why) => expansion_failed(&why),
+        },
+    };
+    if pipeline.negated {
+        shell_state.condition_depth -= 1;
+        status = if status.success() { ExitStatus::FAILURE } else { ExitStatus::SUCCESS };
+    }
+    shell_state.exit_status = status;
+    status
+}
+
+/// Runs the pipelines of an and-or list, skipping them according to the `&&` and `||` between them.
+/// Stops early if `break` or `continue` was used.
+/// If the pipeline that ran last failed, and nothing checks its exit status, the `ERR` trap runs.
+/// With `errexit`, the shell then exits as well.
+fn run_and_or(shell_state: &mut ShellState, list: &AndOrList, streams: &Streams) -> ExitStatus {
+    let mut status = run_pipeline(shell_state, &list.first, streams);
+    let mut last = &list.first;
+    // The exit status of every pipeline but the last one is checked by the `&&` or `||` after it.
+    let mut checked = !list.rest.is_empty();
+    for (idx, (connector, pipeline)) in list.rest.iter().enumerate() {
+        if shell_state.control_flow.is_some() {
+            break;
+        }
+        let should_run = match connector {
+            Connector::And => status.success(),
+            Connector::Or => !status.success(),
+        };
+        if should_run {
+            status = run_pipeline(shell_state, pipeline, streams);
+            last = pipeline;
+            checked = idx + 1 < list.rest.len();
+        }
+    }
+    // A compound command that failed already ran the trap for the command in it that failed.
+    let compound = matches!(last.commands.as_slice(), [Command::Compound(..)]);
+    // The status of a pipeline with `!` is checked by the `!` itself.
+    let unchecked = !checked && !last.negated && !compound && shell_state.condition_depth == 0;
+    if !status.success() && unchecked && shell_state.control_flow.is_none() {
+        run_trap(shell_state, Condition::Err);
+        // The trap can use `exit` itself.
+        if shell_state.options.is_set(ShellOption::Errexit) && shell_state.control_flow.is_none() {
+            shell_state.control_flow = Some(ControlFlow::Exit(status.code));
+        }
+    }
+    status
+}
+
+/// Starts an and-or list that ended with `&` as a job, without waiting for it.
+/// A single pipeline gets its own process group with job control, so it doesn't
+/// get the signals from the terminal. Longer lists, and pipelines with `!` whose
+/// exit status needs to be inverted, run on their own thread instead. They run in a subshell,
+/// so a `cd` in them, like in `cd /tmp && make &`, doesn't change the shell's working directory.
+fn run_background(shell_state: &mut ShellState, list: &AndOrList, streams: &Streams) -> ExitStatus {
+    let mut streams = match streams.try_clone() {
+        Ok(streams) => streams,
+        Err(why) => return ShellError::io("could not duplicate the streams", why).report(),
+    };
+    // Without job control, background jobs can't read from the terminal.
+    if !shell_state.job_control && matches!(streams.stdin, Input::Stdin) {
+        streams.stdin = Input::Closed;
+    }
+    let job = if list.rest.is_empty() && !list.first.negated {
+        match PipedShellCommand::expand(shell_state, &list.first) {
+            Ok(pipe) => start_pipeline(shell_state, &pipe, streams, false),
+            Err(why) => return expansion_failed(&why),
+        }
+    } else {
+        let mut command = list.first.text.clone();
+        for (connector, pipeline) in &list.rest {
+            let connector = match connector {
+                Connector::And => "&&",
+                Connector::Or => "||",
+            };
+            command.push_str(&format!(" {} {}", connector, pipeline.text));
+        }
+        let list = list.clone();
+        let mut subshell = shell_state.subshell();
+        let handle = std::thread::spawn(move || run_and_or(&mut subshell, &list, &streams));
+        Job::new(command, None, vec![Process::Thread(handle)])
+    };
+    let pid = job.processes.iter().find_map(|process| match process {
+        Process::External { child, .. } => Some(child.id()),
+        _ => None,
+    });
+    let id = shell_state.jobs.add(job);
+    if shell_state.job_control {
+        match pid {
+            Some(pid) => eprintln!("[{}] {}", id, pid),
+            None => eprintln!("[{}]", id),
+        }
+    }
+    ExitStatus::SUCCESS
+}
+
+/// Runs every list in the program in order with the given streams.
+/// Stops early if `break` or `continue` was used, or if Ctrl-C was pressed.
+/// Returns the status of the last pipeline that was ran.
+pub fn execute(shell_state: &mut ShellState, program: &Program, streams: &Streams) -> ExitStatus {
+    let mut status = shell_state.exit_status;
+    for list in &program.lists {
+        if shell_state.control_flow.is_some() {
+            break;
+        }
+        if signals::interrupted() {
+            status = INTERRUPTED;
+            shell_state.exit_status = status;
+            break;
+        }
+        status = if list.background {
+            run_background(shell_state, list, streams)
+        } else {
+            run_and_or(shell_state, list, streams)
+        };
+        shell_state.exit_status = status;
+        run_pending(shell_state);
+        // Background jobs that are done get reaped between lists, so they don't stay zombies in scripts.
+        // One that is done while a command runs in the foreground is reaped after it.
+        // Subshells run in threads of the same process, so only the shell itself takes the flag.
+        if !shell_state.subshell && signals::take_child_changed() {
+            shell_state.jobs.update();
+        }
+    }
+    status
+}
+
+/// Runs every list in the program in order, returning the status of the last one.
+pub fn run_program(shell_state: &mut ShellState, program: &Program) -> ExitStatus {
+    execute(shell_state, program, &Streams::default())
+}
+
+/// Handles a pending `break` or `continue` at the end of a loop iteration,
+/// returning `true` if the loop should stop.
+fn loop_should_stop(shell_state: &mut ShellState) -> bool {
+    match shell_state.control_flow {
+        None => false,
+        Some(ControlFlow::Continue(1)) => {
+            shell_state.control_flow = None;
+            false
+        }
+        Some(ControlFlow::Break(1)) => {
+            shell_state.control_flow = None;
+            true
+        }
+        // The `break` or `continue` is meant for an enclosing loop.
+        Some(ControlFlow::Break(count)) => {
+            shell_state.control_flow = Some(ControlFlow::Break(count - 1));
+            true
+        }
+        Some(ControlFlow::Continue(count)) => {
+            shell_state.control_flow = Some(ControlFlow::Continue(count - 1));
+            true
+        }
+        Some(ControlFlow::Return | ControlFlow::Exit(_)) => true,
+    }
+}
+
+/// Runs the body of a loop once for as long as `next` returns `true`,
+/// keeping track of the loop depth for `break` and `continue`.
+fn repeat<F>(shell_state: &mut ShellState, body: &Program, streams: &Streams, mut next: F) -> ExitStatus
+where
+    F: FnMut(&mut ShellState) -> Option<bool>,
+{
+    let mut status = ExitStatus::SUCCESS;
+    shell_state.loop_depth += 1;
+    loop {
+        if signals::interrupted() {
+            break;
+        }
+        match next(shell_state) {
+            Some(true) => (),
+            Some(false) => break,
+            // `next` failed, e.g. because `break` was used in a condition.
+            None => {
+                status = shell_state.exit_status;
+                if loop_should_stop(shell_state) {
+                    break;
+                }
+                continue;
+            }
+        }
+        status = execute(shell_state, body, streams);
+        if loop_should_stop(shell_state) {
+            break;
+        }
+    }
+    shell_state.loop_depth -= 1;
+    if signals::interrupted() {
+        return INTERRUPTED;
+    }
+    status
+}
+
+/// Runs the condition of an `if`, `while` or `until`, whose commands don't run the `ERR` trap when they fail.
+fn run_condition(shell_state: &mut ShellState, condition: &Program, streams: &Streams) -> ExitStatus {
+    shell_state.condition_depth += 1;
+    let status = execute(shell_state, condition, streams);
+    shell_state.condition_depth -= 1;
+    status
+}
+
+/// Runs an `if`, `while`, `until`, `for` or `case` command, returning its exit status.
+pub fn run_compound(shell_state: &mut ShellState, command: &CompoundCommand, streams: &Streams) -> ExitStatus {
+    match command {
+        CompoundCommand::If { branches, else_branch } => {
+            for (condition, body) in branches {
+                let condition = run_condition(shell_state, condition, streams);
+                if shell_state.control_flow.is_some() {
+                    return condition;
+                }
+                if condition.success() {
+                    return execute(shell_state, body, streams);
+                }
+            }
+            match else_branch {
+                Some(body) => execute(shell_state, body, streams),
+                None => ExitStatus::SUCCESS,
+            }
+        }
+        CompoundCommand::While { condition, body, until } => repeat(shell_state, body, streams, |shell_state| {
+            let status = run_condition(shell_state, condition, streams);
+            if shell_state.control_flow.is_some() {
+                None
+            } else {
+                Some(status.success() != *until)
+            }
+        }),
+        CompoundCommand::For { name, words, body } => {
+            let values = match words {
+                Some(words) => match expand_words(shell_state, words) {
+                    Ok(values) => values,
+                    Err(why) => return expansion_failed(&why),
+                },
+                None => shell_state.positional.clone(),
+            };
+            let mut values = values.into_iter();
+            repeat(shell_state, body, streams, |shell_state| match values.next() {
+                Some(value) => {
+                    shell_state.variables.set(name, &value);
+                    Some(true)
+                }
+                None => Some(false),
+            })
+        }
+        CompoundCommand::Case { word, arms } => {
+            let subject = match expand_word(shell_state, word) {
+                Ok(subject) => subject,
+                Err(why) => return expansion_failed(&why),
+            };
+            for arm in arms {
+                for pattern in &arm.patterns {
+                    let pattern = match expand_pattern(shell_state, pattern) {
+                        Ok(pattern) => pattern,
+                        Err(why) => return expansion_failed(&why),
+                    };
+                    if glob::matches(&pattern, &subject) {
+                        return execute(shell_state, &arm.body, streams);
+                    }
+                }
+            }
+            ExitStatus::SUCCESS
+        }
+        CompoundCommand::BraceGroup(body) => execute(shell_state, body, streams),
+    }
+}
+
+/// Calls a function with `args` as its positional parameters, returning its exit status.
+/// Variables made local with `local` are restored afterwards.
+pub fn call_function(
+    shell_state: &mut ShellState,
+    function: &FunctionDefinition,
+    args: Vec<String>,
+    mut streams: Streams,
+) -> ExitStatus {
+    let redirections = match expand_redirects(shell_state, &function.redirects) {
+        Ok(redirections) => redirections,
+        Err(why) => return expansion_failed(&why),
+    };
+    if let Err(why) = streams.redirect_all(&redirections, &shell_state.cwd) {
+        return why.report();
+    }
+    let positional = std::mem::replace(&mut shell_state.positional, args);
+    // `break` and `continue` can't leave loops outside of the function.
+    let loop_depth = std::mem::replace(&mut shell_state.loop_depth, 0);
+    shell_state.function_depth += 1;
+    shell_state.variables.push_scope();
+    let status = run_compound(shell_state, &function.body, &streams);
+    shell_state.variables.pop_scope();
+    shell_state.function_depth -= 1;
+    shell_state.loop_depth = loop_depth;
+    shell_state.positional = positional;
+    if shell_state.control_flow == Some(ControlFlow::Return) {
+        shell_state.control_flow = None;
+    }
+    status
+}
+
+/// Runs the program like a subshell, returning everything the commands printed.
+/// The commands can't change the `ShellState`, apart from the exit status.
+pub fn program_output(shell_state: &mut ShellState, program: &Program) -> String {
+    let (mut reader, writer) = match std::io::pipe() {
+        Ok(pipe) => pipe,
+        Err(why) => {
+            ShellError::io("could not create a pipe", why).report();
+            return String::new();
+        }
+    };
+    // Read while the commands run, so they don't block on a full pipe.
+    let output = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = reader.read_to_end(&mut output);
+        String::from_utf8_lossy(&output).into_owned()
+    });
+    let mut subshell = shell_state.subshell();
+    let streams = Streams {
+        stdout: Output::Pipe(writer),
+        ..Streams::default()
+    };
+    shell_state.exit_status = execute(&mut subshell, program, &streams);
+    drop(streams);
+    output.join().unwrap_or_default()
+}
@@ -0,0 +1,534 @@
+use crate::arithmetic::evaluate;
+use crate::braces::expand_braces;
+use crate::executor::program_output;
+use crate::glob;
+use crate::lexer::{ParamOp, Parameter, ReplaceMode, Word, WordPart};
+use crate::options::ShellOption;
+use crate::parser::Program;
+use crate::shared_functions::ShellState;
+use crate::variables::is_valid_name;
+use std::borrow::Cow;
+
+/// Returns the value of a parameter, or `None` if it is not set.
+/// `$@` and `$*` are all positional parameters, joined by a space
+/// or by the first character of `$IFS` respectively.
+fn parameter_value(shell_state: &ShellState, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(shell_state.exit_status.code.to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "#" => Some(shell_state.positional.len().to_string()),
+        "@" => Some(shell_state.positional.join(" ")),
+        "*" => {
+            let ifs = shell_state.variables.get("IFS").unwrap_or(" ");
+            let separator = ifs.chars().next().map(String::from).unwrap_or_default();
+            Some(shell_state.positional.join(&separator))
+        }
+        "0" => Some(shell_state.arg0.clone()),
+        name => match name.parse::<usize>() {
+            Ok(position) => position.checked_sub(1).and_then(|idx| shell_state.positional.get(idx)).cloned(),
+            Err(_) => shell_state.variables.get(name).map(String::from),
+        },
+    }
+}
+
+/// Runs a command substitution and returns its output, without the trailing newlines.
+fn command_output(shell_state: &mut ShellState, program: &Program) -> String {
+    let mut output = program_output(shell_state, program);
+    output.truncate(output.trim_end_matches('\n').len());
+    output
+}
+
+/// Expands and evaluates the expression of an arithmetic expansion.
+fn arithmetic(shell_state: &mut ShellState, expression: &Word) -> Result<String, String> {
+    let expression = expand_word(shell_state, expression)?;
+    evaluate(&expression, &mut shell_state.variables, true)
+        .map(|result| result.to_string())
+        .map_err(|why| format!("{}: {}", expression.trim(), why))
+}
+
+/// A field that a word expanded to, along with the pattern used for pathname expansion,
+/// in which everything that was quoted is escaped.
+#[derive(Default)]
+struct Field {
+    value: String,
+    pattern: String,
+}
+
+impl Field {
+    fn push_str(&mut self, text: &str, quoted: bool) {
+        self.value.push_str(text);
+        if quoted {
+            self.pattern.push_str(&glob::escape(text));
+        } else {
+            self.pattern.push_str(text);
+        }
+    }
+}
+
+/// Splits the result of an unquoted expansion into fields, at every character in `$IFS`.
+/// The first field is appended to `current`, the field the word was building up so far.
+/// Whitespace separators are merged and ignored at the start and end, others separate every time.
+fn split_fields(text: &str, ifs: &str, current: &mut Option<Field>, fields: &mut Vec<Field>) {
+    // Whether the last character was a whitespace separator, which a separator like `:` then belongs to.
+    let mut after_whitespace = false;
+    for c in text.chars() {
+        if !ifs.contains(c) {
+            current.get_or_insert_with(Field::default).push_str(c.encode_utf8(&mut [0; 4]), false);
+            after_whitespace = false;
+        } else if c.is_whitespace() {
+            if let Some(field) = current.take() {
+                fields.push(field);
+                after_whitespace = true;
+            }
+        } else {
+            if !after_whitespace {
+                fields.push(current.take().unwrap_or_default());
+            }
+            after_whitespace = false;
+        }
+    }
+}
+
+/// Expands a word into a glob pattern. Quoted parts are escaped, so they only match themselves.
+pub fn expand_pattern(shell_state: &mut ShellState, word: &Word) -> Result<String, String> {
+    let mut pattern = String::new();
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => pattern.push_str(text),
+            WordPart::Quoted(text) => pattern.push_str(&glob::escape(text)),
+            WordPart::DoubleQuoted(parts) => pattern.push_str(&glob::escape(&expand_parts(shell_state, parts)?)),
+            WordPart::Param(parameter) => pattern.push_str(&expand_parameter(shell_state, parameter)?),
+            WordPart::Command(program) => pattern.push_str(&command_output(shell_state, program)),
+            WordPart::Arithmetic(expression) => pattern.push_str(&arithmetic(shell_state, expression)?),
+        }
+    }
+    Ok(pattern)
+}
+
+/// Returns the byte offset of every char boundary in `text`, including its end.
+fn boundaries(text: &str) -> Vec<usize> {
+    text.char_indices().map(|(idx, _)| idx).chain(std::iter::once(text.len())).collect()
+}
+
+/// Removes the shortest (or longest) prefix of `value` that matches `pattern`.
+fn remove_prefix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut ends = boundaries(value);
+    if longest {
+        ends.reverse();
+    }
+    match ends.into_iter().find(|end| glob::matches(pattern, &value[..*end])) {
+        Some(end) => value[end..].to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Removes the shortest (or longest) suffix of `value` that matches `pattern`.
+fn remove_suffix(value: &str, pattern: &str, longest: bool) -> String {
+    let mut starts = boundaries(value);
+    if !longest {
+        starts.reverse();
+    }
+    match starts.into_iter().find(|start| glob::matches(pattern, &value[*start..])) {
+        Some(start) => value[..start].to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Replaces the longest non-empty matches of `pattern` in `value` with `replacement`.
+fn replace(value: &str, pattern: &str, replacement: &str, mode: ReplaceMode) -> String {
+    let bounds = boundaries(value);
+    let mut replaced = String::new();
+    // Where the part of `value` that was not yet copied to `replaced` starts.
+    let mut copied = 0;
+    let mut idx = 0;
+    while idx < bounds.len() {
+        let start = bounds[idx];
+        if start < copied {
+            idx += 1;
+            continue;
+        }
+        let found = bounds[idx + 1..].iter().rev().find(|end| {
+            (mode != ReplaceMode::Suffix || **end == value.len()) && glob::matches(pattern, &value[start..**end])
+        });
+        if let Some(end) = found {
+            replaced.push_str(&value[copied..start]);
+            replaced.push_str(replacement);
+            copied = *end;
+            if mode != ReplaceMode::All {
+                break;
+            }
+        }
+        if mode == ReplaceMode::Prefix {
+            break;
+        }
+        idx += 1;
+    }
+    replaced.push_str(&value[copied..]);
+    replaced
+}
+
+/// Expands a parameter, applying its operator if it has one.
+/// Fails for `${var:?message}` when `var` is unset or empty, and when assigning to a special parameter.
+/// With `nounset`, it also fails when `var` is unset, unless the operator gives it a default.
+fn expand_parameter(shell_state: &mut ShellState, parameter: &Parameter) -> Result<String, String> {
+    let name = parameter.name.as_str();
+    let value = parameter_value(shell_state, name);
+    let has_default = matches!(
+        parameter.op,
+        Some(ParamOp::Default { .. } | ParamOp::Assign { .. } | ParamOp::Error { .. } | ParamOp::Alternative { .. })
+    );
+    if value.is_none() && shell_state.options.is_set(ShellOption::Nounset) && !has_default {
+        return Err(format!("{}: unbound variable", name));
+    }
+    // Whether the value counts as unset for an operator with or without a colon.
+    let is_unset = |colon: bool| match &value {
+        Some(value) => colon && value.is_empty(),
+        None => true,
+    };
+    let op = match &parameter.op {
+        Some(op) => op,
+        None => return Ok(value.unwrap_or_default()),
+    };
+    Ok(match op {
+        ParamOp::Length => value.unwrap_or_default().chars().count().to_string(),
+        ParamOp::Default { colon, word } if is_unset(*colon) => expand_word(shell_state, word)?,
+        ParamOp::Assign { colon, word } if is_unset(*colon) => {
+            if !is_valid_name(name) {
+                return Err(format!("${}: cannot assign in this way", name));
+            }
+            let default = expand_word(shell_state, word)?;
+            shell_state.variables.set(name, &default);
+            default
+        }
+        ParamOp::Error { colon, word } if is_unset(*colon) => {
+            let message = expand_word(shell_state, word)?;
+            if message.is_empty() {
+                return Err(format!("{}: parameter null or not set", name));
+            }
+            return Err(format!("{}: {}", name, message));
+        }
+        ParamOp::Alternative { colon, word } => {
+            if is_unset(*colon) {
+                String::new()
+            } else {
+                expand_word(shell_state, word)?
+            }
+        }
+        ParamOp::Default { .. } | ParamOp::Assign { .. } | ParamOp::Error { .. } => value.unwrap_or_default(),
+        ParamOp::RemovePrefix { longest, pattern } => {
+            let pattern = expand_pattern(shell_state, pattern)?;
+            remove_prefix(&value.unwrap_or_default(), &pattern, *longest)
+        }
+        ParamOp::RemoveSuffix { longest, pattern } => {
+            let pattern = expand_pattern(shell_state, pattern)?;
+            remove_suffix(&value.unwrap_or_default(), &pattern, *longest)
+        }
+        ParamOp::Replace {
+            mode,
+            pattern,
+            replacement,
+        } => {
+            let pattern = expand_pattern(shell_state, pattern)?;
+            let replacement = expand_word(shell_state, replacement)?;
+            replace(&value.unwrap_or_default(), &pattern, &replacement, *mode)
+        }
+    })
+}
+
+/// Returns the home directory of `user` from the password database.
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<String> {
+    let name = std::ffi::CString::new(user).ok()?;
+    // Safety: `passwd` is plain data, which `getpwnam_r` fills in with pointers into `buf`.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    let ret = unsafe { libc::getpwnam_r(name.as_ptr(), &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() || passwd.pw_dir.is_null() {
+        return None;
+    }
+    let home = unsafe { std::ffi::CStr::from_ptr(passwd.pw_dir) };
+    Some(home.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+fn user_home(_user: &str) -> Option<String> {
+    None
+}
+
+/// Expands a `~` at the start of a word, up until the first `/`.
+/// `~` is the user's home directory, and `~name` is the home directory of the user `name`.
+/// Returns the directory and the rest of the word, or `None` if there is nothing to expand.
+fn expand_tilde(shell_state: &ShellState, word: &Word) -> Option<(String, Word)> {
+    let literal = match word.parts.first() {
+        Some(WordPart::Literal(literal)) if literal.starts_with('~') => literal,
+        _ => return None,
+    };
+    let end = match literal.find('/') {
+        Some(end) => end,
+        // Something like `~"name"` isn't expanded.
+        None if word.parts.len() == 1 => literal.len(),
+        None => return None,
+    };
+    let user = &literal[1..end];
+    let home = if user.is_empty() {
+        shell_state.variables.get("HOME").unwrap_or(&shell_state.home).to_string()
+    } else {
+        user_home(user)?
+    };
+    let mut rest = word.clone();
+    rest.parts[0] = WordPart::Literal(literal[end..].to_string());
+    Some((home, rest))
+}
+
+/// Expands a word into the string it stands for, by replacing every
+/// parameter and command substitution with its value and removing the quotes.
+/// This is used where a word stays a single word, like in assignments and redirections.
+pub fn expand_word(shell_state: &mut ShellState, word: &Word) -> Result<String, String> {
+    match expand_tilde(shell_state, word) {
+        Some((home, rest)) => Ok(home + &expand_parts(shell_state, &rest.parts)?),
+        None => expand_parts(shell_state, &word.parts),
+    }
+}
+
+/// Expands the parts of a word and joins them, see `expand_word`.
+fn expand_parts(shell_state: &mut ShellState, parts: &[WordPart]) -> Result<String, String> {
+    let mut expanded = String::new();
+    for part in parts {
+        match part {
+            WordPart::Literal(text) | WordPart::Quoted(text) => expanded.push_str(text),
+            WordPart::DoubleQuoted(parts) => expanded.push_str(&expand_parts(shell_state, parts)?),
+            WordPart::Param(parameter) => expanded.push_str(&expand_parameter(shell_state, parameter)?),
+            WordPart::Command(program) => expanded.push_str(&command_output(shell_state, program)),
+            WordPart::Arithmetic(expression) => expanded.push_str(&arithmetic(shell_state, expression)?),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Expands a word like `expand_word`, but splits the results of unquoted expansions
+/// into separate fields. A word can expand to no fields at all,
+/// e.g. when the command printed nothing.
+fn expand_fields(shell_state: &mut ShellState, word: &Word) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    let mut current: Option<Field> = None;
+    let word = match expand_tilde(shell_state, word) {
+        Some((home, rest)) => {
+            // The home directory is taken literally, and isn't split or globbed.
+            current.get_or_insert_with(Field::default).push_str(&home, true);
+            Cow::Owned(rest)
+        }
+        None => Cow::Borrowed(word),
+    };
+    for part in &word.parts {
+        match part {
+            WordPart::Literal(text) => current.get_or_insert_with(Field::default).push_str(text, false),
+            WordPart::Quoted(text) => current.get_or_insert_with(Field::default).push_str(text, true),
+            WordPart::DoubleQuoted(parts) => {
+                // Even `""` is a field, but `"$@"` without any positional parameters isn't.
+                if parts.is_empty() {
+                    current.get_or_insert_with(Field::default);
+                }
+                for part in parts {
+                    match part {
+                        // `"$@"` expands to one field per positional parameter.
+                        WordPart::Param(Parameter { name, op: None }) if name == "@" => {
+                            for (idx, arg) in shell_state.positional.iter().enumerate() {
+                                if idx > 0 {
+                                    fields.extend(current.take());
+                                }
+                                current.get_or_insert_with(Field::default).push_str(arg, true);
+                            }
+                        }
+                        part => {
+                            let text = expand_parts(shell_state, std::slice::from_ref(part))?;
+                            current.get_or_insert_with(Field::default).push_str(&text, true);
+                        }
+                    }
+                }
+            }
+            WordPart::Param(_) | WordPart::Arithmetic(_) | WordPart::Command(_) => {
+                let text = expand_parts(shell_state, std::slice::from_ref(part))?;
+                let ifs = shell_state.variables.get("IFS").unwrap_or(" \t\n").to_string();
+                split_fields(&text, &ifs, &mut current, &mut fields);
+            }
+        }
+    }
+    fields.extend(current);
+    Ok(fields)
+}
+
+/// Expands the braces in every word, then expands the words into their fields, see
+/// `expand_fields`, and finally replaces the fields that contain an unquoted `*`, `?` or `[`
+/// with the paths they match.
+/// A pattern without any matches is left as-is, unless `nullglob` or `failglob` is set.
+/// With `noglob`, patterns are always left as-is.
+pub fn expand_words(shell_state: &mut ShellState, words: &[Word]) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    for word in words.iter().flat_map(expand_braces) {
+        for field in expand_fields(shell_state, &word)? {
+            if shell_state.options.is_set(ShellOption::Noglob) || !glob::has_glob(&field.pattern) {
+                expanded.push(field.value);
+                continue;
+            }
+            let paths = glob::expand(&field.pattern, &shell_state.cwd);
+            if !paths.is_empty() {
+                expanded.extend(paths);
+            } else if shell_state.options.is_set(ShellOption::Failglob) {
+                return Err(format!("no match: {}", field.value));
+            } else if !shell_state.options.is_set(ShellOption::Nullglob) {
+                expanded.push(field.value);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ExitStatus;
+    use crate::jobs::Jobs;
+    use crate::lexer::{lex, TokenKind};
+    use crate::options::Options;
+    use crate::traps::Traps;
+    use crate::variables::Variables;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    /// Lexes `input` as a single word and expands it.
+    fn expand(shell_state: &mut ShellState, input: &str) -> Result<String, String> {
+        match lex(input).unwrap().into_iter().next().map(|token| token.kind) {
+            Some(TokenKind::Word(word)) => expand_word(shell_state, &word),
+            _ => panic!("`{}` is not a word", input),
+        }
+    }
+
+    /// A shell with only the variables the tests need, which doesn't touch the environment
+    /// or the file system like `ShellState::init` does.
+    fn shell_state() -> ShellState {
+        let mut variables = Variables::default();
+        variables.set("path", "/usr/local/lib/file.tar.gz");
+        variables.set("empty", "");
+        ShellState {
+            args: Vec::new(),
+            prompt: String::new(),
+            user: String::from("user"),
+            home: String::from("/home/user"),
+            na: String::new(),
+            share_dir: String::new(),
+            cd_prev_dir: None,
+            cwd: PathBuf::from("/"),
+            config_dir: String::new(),
+            config: String::new(),
+            history_file: String::new(),
+            edit_mode: String::new(),
+            bell_style: String::new(),
+            history_auto_add_lines: false,
+            history_size: 0,
+            history_spaces_ignored: false,
+            exit_status: ExitStatus::SUCCESS,
+            arg0: String::from("crust"),
+            positional: Vec::new(),
+            variables,
+            options: Options::default(),
+            control_flow: None,
+            loop_depth: 0,
+            functions: BTreeMap::new(),
+            function_depth: 0,
+            jobs: Jobs::default(),
+            job_control: false,
+            traps: Traps::default(),
+            condition_depth: 0,
+            prompt_error_reported: false,
+            subshell: false,
+        }
+    }
+
+    #[test]
+    fn length() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${#path}").unwrap(), "26");
+        assert_eq!(expand(&mut shell_state, "${#unset}").unwrap(), "0");
+    }
+
+    #[test]
+    fn default() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${unset:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand(&mut shell_state, "${empty:-fallback}").unwrap(), "fallback");
+        assert_eq!(expand(&mut shell_state, "${empty-fallback}").unwrap(), "");
+        assert_eq!(expand(&mut shell_state, "${unset-$empty}").unwrap(), "");
+        assert_eq!(expand(&mut shell_state, "${path:-fallback}").unwrap(), "/usr/local/lib/file.tar.gz");
+    }
+
+    #[test]
+    fn assign() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${unset:=value}").unwrap(), "value");
+        assert_eq!(shell_state.variables.get("unset"), Some("value"));
+        assert_eq!(expand(&mut shell_state, "${empty=value}").unwrap(), "");
+        assert_eq!(shell_state.variables.get("empty"), Some(""));
+    }
+
+    #[test]
+    fn error() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${unset:?custom message}").unwrap_err(), "unset: custom message");
+        assert_eq!(expand(&mut shell_state, "${empty:?}").unwrap_err(), "empty: parameter null or not set");
+        assert_eq!(expand(&mut shell_state, "${empty?}").unwrap(), "");
+    }
+
+    #[test]
+    fn alternative() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${path:+set}").unwrap(), "set");
+        assert_eq!(expand(&mut shell_state, "${empty:+set}").unwrap(), "");
+        assert_eq!(expand(&mut shell_state, "${empty+set}").unwrap(), "set");
+        assert_eq!(expand(&mut shell_state, "${unset+set}").unwrap(), "");
+    }
+
+    #[test]
+    fn remove_prefix() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${path#*/}").unwrap(), "usr/local/lib/file.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path##*/}").unwrap(), "file.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path#nomatch}").unwrap(), "/usr/local/lib/file.tar.gz");
+    }
+
+    #[test]
+    fn remove_suffix() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${path%.*}").unwrap(), "/usr/local/lib/file.tar");
+        assert_eq!(expand(&mut shell_state, "${path%%.*}").unwrap(), "/usr/local/lib/file");
+        assert_eq!(expand(&mut shell_state, "${path%/*}").unwrap(), "/usr/local/lib");
+    }
+
+    #[test]
+    fn quoted_patterns_are_literal() {
+        let mut shell_state = shell_state();
+        shell_state.variables.set("stars", "a*b*c");
+        assert_eq!(expand(&mut shell_state, "${stars#'a*'}").unwrap(), "b*c");
+        assert_eq!(expand(&mut shell_state, "${stars#a*}").unwrap(), "*b*c");
+    }
+
+    #[test]
+    fn replace() {
+        let mut shell_state = shell_state();
+        assert_eq!(expand(&mut shell_state, "${path/l/L}").unwrap(), "/usr/Local/lib/file.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path//l/L}").unwrap(), "/usr/LocaL/Lib/fiLe.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path/#'/usr'/@}").unwrap(), "@/local/lib/file.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path/%.gz/.xz}").unwrap(), "/usr/local/lib/file.tar.xz");
+        assert_eq!(expand(&mut shell_state, "${path/#local/x}").unwrap(), "/usr/local/lib/file.tar.gz");
+        assert_eq!(expand(&mut shell_state, "${path//[.-]/_}").unwrap(), "/usr/local/lib/file_tar_gz");
+        assert_eq!(expand(&mut shell_state, "${path/l*'/'/}").unwrap(), "/usr/file.tar.gz");
+    }
+
+    #[test]
+    fn bad_substitution() {
+        assert!(lex("${path:x}").is_err());
+        assert!(lex("${#path:-x}").is_err());
+        assert!(lex("${path").is_err());
+        assert!(lex("${path:-x").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::Path;
+
+/// Checks if the bracket expression at the start of `pattern` (e.g. `[a-z]`) matches `c`.
+/// Returns whether it matched and how long the bracket expression is,
+/// or `None` if it is not closed, in which case the `[` is taken literally.
+fn match_bracket(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut idx = 1;
+    let negated = matches!(pattern.get(idx), Some('!') | Some('^'));
+    if negated {
+        idx += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let current = *pattern.get(idx)?;
+        // A `]` right at the start is part of the set.
+        if current == ']' && !first {
+            break;
+        }
+        first = false;
+        if current == '[' && pattern.get(idx + 1) == Some(&':') {
+            let rest: String = pattern[idx + 2..].iter().collect();
+            if let Some(end) = rest.find(":]") {
+                let class = &rest[..end];
+                matched |= match class {
+                    "alnum" => c.is_alphanumeric(),
+                    "alpha" => c.is_alphabetic(),
+                    "blank" => c == ' ' || c == '\t',
+                    "digit" => c.is_ascii_digit(),
+                    "lower" => c.is_lowercase(),
+                    "punct" => c.is_ascii_punctuation(),
+                    "space" => c.is_whitespace(),
+                    "upper" => c.is_uppercase(),
+                    "xdigit" => c.is_ascii_hexdigit(),
+                    _ => false,
+                };
+                idx += 2 + class.chars().count() + 2;
+                continue;
+            }
+        }
+        let start = if current == '\\' {
+            idx += 1;
+            *pattern.get(idx)?
+        } else {
+            current
+        };
+        idx += 1;
+        // A range like `a-z`, but `-` right before the `]` is taken literally.
+        if pattern.get(idx) == Some(&'-') && pattern.get(idx + 1).is_some_and(|end| *end != ']') {
+            let mut end = pattern[idx + 1];
+            idx += 2;
+            if end == '\\' {
+                end = *pattern.get(idx)?;
+                idx += 1;
+            }
+            matched |= start <= c && c <= end;
+        } else {
+            matched |= start == c;
+        }
+    }
+    Some((matched != negated, idx + 1))
+}
+
+/// Checks if all of `text` matches the glob `pattern`.
+/// Supports `*`, `?`, bracket expressions like `[a-z]`, `[!0-9]` or `[[:digit:]]`,
+/// and `\` to take the next character literally.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p_idx, mut t_idx) = (0, 0);
+    // Where the last `*` was in the pattern, and where in the text it started matching.
+    let mut star: Option<(usize, usize)> = None;
+    while t_idx < text.len() {
+        let step = match pattern.get(p_idx) {
+            Some('*') => {
+                star = Some((p_idx, t_idx));
+                p_idx += 1;
+                continue;
+            }
+            Some('?') => Some(1),
+            Some('[') => match match_bracket(&pattern[p_idx..], text[t_idx]) {
+                Some((true, len)) => Some(len),
+                Some((false, _)) => None,
+                None => Some(1).filter(|_| text[t_idx] == '['),
+            },
+            Some('\\') if p_idx + 1 < pattern.len() => Some(2).filter(|_| pattern[p_idx + 1] == text[t_idx]),
+            Some(c) => Some(1).filter(|_| *c == text[t_idx]),
+            None => None,
+        };
+        match (step, star) {
+            (Some(len), _) => {
+                p_idx += len;
+                t_idx += 1;
+            }
+            // Let the last `*` match one more character, and try again from there.
+            (None, Some((star_p_idx, star_t_idx))) => {
+                p_idx = star_p_idx + 1;
+                t_idx = star_t_idx + 1;
+                star = Some((star_p_idx, t_idx));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p_idx..].iter().all(|c| *c == '*')
+}
+
+/// Escapes every character that has a special meaning in a glob pattern.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Checks if `pattern` has any unescaped `*`, `?` or `[`, and therefore needs to be expanded.
+pub fn has_glob(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' => return true,
+            '[' if chars.as_str().contains(']') => return true,
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Removes the backslashes from a pattern without any globs.
+fn unescape(pattern: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Appends `name` to `path`, where an empty `path` is the current directory.
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else if path.ends_with('/') {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
+/// Returns the names in the directory at `path`, which is relative to `cwd`,
+/// or nothing if it can't be read.
+fn read_dir_names(cwd: &Path, path: &str) -> Vec<String> {
+    match std::fs::read_dir(cwd.join(path)) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Pushes everything below `path` to `paths`, or only the directories if `files` is `false`.
+/// Hidden files are skipped, and symlinks aren't followed, so there can't be any loops.
+fn walk(cwd: &Path, path: &str, files: bool, paths: &mut Vec<String>) {
+    for name in read_dir_names(cwd, path) {
+        if name.starts_with('.') {
+            continue;
+        }
+        let entry = join(path, &name);
+        let is_dir = std::fs::symlink_metadata(cwd.join(&entry)).is_ok_and(|metadata| metadata.is_dir());
+        if is_dir || files {
+            paths.push(entry.clone());
+        }
+        if is_dir {
+            walk(cwd, &entry, files, paths);
+        }
+    }
+}
+
+/// Returns every path that matches `pattern`, sorted. Each `/`-separated component of
+/// the pattern is matched against the names in a directory, where names starting with a `.`
+/// only match if the component does too. A component that is just `**` matches any number
+/// of directories, or all files and directories below it if it is the last one.
+/// Relative patterns are matched in `cwd`, but the paths stay relative.
+pub fn expand(pattern: &str, cwd: &Path) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') { String::from("/") } else { String::new() }];
+    let components: Vec<&str> = pattern.split('/').filter(|component| !component.is_empty()).collect();
+    for (idx, component) in components.iter().enumerate() {
+        let last = idx == components.len() - 1;
+        let mut next = Vec::new();
+        for path in &paths {
+            if *component == "**" {
+                if !last {
+                    next.push(path.clone());
+                }
+                walk(cwd, path, last, &mut next);
+            } else if !has_glob(component) {
+                next.push(join(path, &unescape(component)));
+            } else {
+                for name in read_dir_names(cwd, path) {
+                    if (name.starts_with('.') && !component.starts_with('.')) || !matches(component, &name) {
+                        continue;
+                    }
+                    next.push(join(path, &name));
+                }
+            }
+        }
+        if !last {
+            next.retain(|path| path.is_empty() || cwd.join(path).is_dir());
+        }
+        paths = next;
+    }
+    paths.retain(|path| !path.is_empty() && std::fs::symlink_metadata(cwd.join(path)).is_ok());
+    // A trailing `/` only matches directories.
+    if pattern.ends_with('/') && !components.is_empty() {
+        paths.retain(|path| cwd.join(path).is_dir());
+        for path in &mut paths {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths.dedup();
+    paths
+}
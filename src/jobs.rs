@@ -0,0 +1,523 @@
+use crate::commands::ExitStatus;
+use crate::shared_functions::ShellState;
+use std::process::Child;
+use std::thread::JoinHandle;
+
+/// A command that is part of a job.
+#[derive(Debug)]
+pub enum Process {
+    /// An external command, with its exit status once it is done.
+    External { child: Child, status: Option<ExitStatus> },
+    /// A builtin, function or compound command running on its own thread.
+    Thread(JoinHandle<ExitStatus>),
+    /// A command that is already done, e.g. because it could not be started.
+    Done(ExitStatus),
+}
+
+impl Process {
+    fn status(&self) -> Option<ExitStatus> {
+        match self {
+            Process::External { status, .. } => *status,
+            Process::Thread(_) => None,
+            Process::Done(status) => Some(*status),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(ExitStatus),
+}
+
+/// A pipeline, or an and-or list that was started with `&`.
+#[derive(Debug)]
+pub struct Job {
+    /// The number used to refer to the job, e.g. `%1`.
+    pub id: usize,
+    /// The command line of the job, as shown by `jobs`.
+    pub command: String,
+    /// The process group of the external commands, if job control is on.
+    pub pgid: Option<u32>,
+    pub processes: Vec<Process>,
+    pub state: JobState,
+    /// The signal that killed the last command, and whether it dumped core.
+    signal: Option<(i32, bool)>,
+    /// Whether any command that failed makes the job fail, like with `set -o pipefail`.
+    pipefail: bool,
+    /// Whether the state changed since it was last reported.
+    changed: bool,
+}
+
+/// What happened to an external command.
+enum Event {
+    Exited(ExitStatus),
+    Killed { signal: i32, core_dumped: bool },
+    Stopped,
+    Continued,
+}
+
+/// Waits for a child to exit or stop, or only checks if it did if `block` is `false`.
+/// Returns `None` if nothing happened yet.
+#[cfg(unix)]
+fn wait_child(child: &mut Child, block: bool) -> Option<Event> {
+    use std::os::unix::process::ExitStatusExt;
+    let flags = if block {
+        libc::WUNTRACED
+    } else {
+        libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED
+    };
+    let mut status = 0;
+    loop {
+        let pid = unsafe { libc::waitpid(child.id() as libc::pid_t, &mut status, flags) };
+        match pid {
+            0 => return None,
+            -1 if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted => continue,
+            // The child can't be waited for anymore, so there is nothing else to do.
+            -1 => return Some(Event::Exited(ExitStatus::FAILURE)),
+            _ => break,
+        }
+    }
+    Some(if libc::WIFSTOPPED(status) {
+        Event::Stopped
+    } else if libc::WIFCONTINUED(status) {
+        Event::Continued
+    } else if libc::WIFSIGNALED(status) {
+        Event::Killed {
+            signal: libc::WTERMSIG(status),
+            core_dumped: libc::WCOREDUMP(status),
+        }
+    } else {
+        Event::Exited(ExitStatus::from(std::process::ExitStatus::from_raw(status)))
+    })
+}
+
+#[cfg(not(unix))]
+fn wait_child(child: &mut Child, block: bool) -> Option<Event> {
+    let status = if block { child.wait().ok() } else { child.try_wait().ok()? };
+    Some(Event::Exited(status.map_or(ExitStatus::FAILURE, ExitStatus::from)))
+}
+
+impl Job {
+    pub fn new(command: String, pgid: Option<u32>, processes: Vec<Process>) -> Job {
+        let mut job = Job {
+            id: 0,
+            command,
+            pgid,
+            processes,
+            state: JobState::Running,
+            signal: None,
+            pipefail: false,
+            changed: false,
+        };
+        job.check_done();
+        job
+    }
+
+    /// Makes the exit status of the job the one of the last command that failed, for `pipefail`.
+    pub fn with_pipefail(mut self, pipefail: bool) -> Job {
+        self.pipefail = pipefail;
+        self.check_done();
+        self
+    }
+
+    /// The exit status of the job, which is the one of its last command.
+    /// With `pipefail`, it is the one of the last command that failed instead, if any did.
+    fn status(&self) -> ExitStatus {
+        if self.pipefail {
+            let failed = self.processes.iter().rev().filter_map(Process::status).find(|status| !status.success());
+            if let Some(status) = failed {
+                return status;
+            }
+        }
+        self.processes.last().and_then(Process::status).unwrap_or_default()
+    }
+
+    fn set_state(&mut self, state: JobState) {
+        if self.state != state {
+            self.state = state;
+            self.changed = true;
+        }
+    }
+
+    fn check_done(&mut self) {
+        if self.processes.iter().all(|process| process.status().is_some()) {
+            let status = self.status();
+            self.set_state(JobState::Done(status));
+        }
+    }
+
+    fn handle(&mut self, idx: usize, event: Event) {
+        match event {
+            Event::Exited(status) => {
+                if let Process::External { status: process_status, .. } = &mut self.processes[idx] {
+                    *process_status = Some(status);
+                }
+            }
+            Event::Killed { signal, core_dumped } => {
+                if let Process::External { status, .. } = &mut self.processes[idx] {
+                    *status = Some(ExitStatus::from_code(128 + signal));
+                }
+                if idx + 1 == self.processes.len() {
+                    self.signal = Some((signal, core_dumped));
+                }
+            }
+            Event::Stopped => self.set_state(JobState::Stopped),
+            Event::Continued => self.set_state(JobState::Running),
+        }
+    }
+
+    /// Waits until every command of the job is done, or until it was stopped, e.g. with Ctrl-Z.
+    pub fn wait(&mut self) -> JobState {
+        for idx in 0..self.processes.len() {
+            let event = match &mut self.processes[idx] {
+                Process::External { child, status: None } => wait_child(child, true),
+                Process::Thread(_) => {
+                    let status = match std::mem::replace(&mut self.processes[idx], Process::Done(ExitStatus::FAILURE)) {
+                        Process::Thread(handle) => handle.join().unwrap_or(ExitStatus::FAILURE),
+                        _ => unreachable!(),
+                    };
+                    self.processes[idx] = Process::Done(status);
+                    None
+                }
+                _ => None,
+            };
+            if let Some(event) = event {
+                self.handle(idx, event);
+                if self.state == JobState::Stopped {
+                    return self.state;
+                }
+            }
+        }
+        self.check_done();
+        self.state
+    }
+
+    /// Checks if any command of the job changed its state, without blocking.
+    pub fn poll(&mut self) {
+        for idx in 0..self.processes.len() {
+            match &mut self.processes[idx] {
+                Process::External { child, status: None } => {
+                    if let Some(event) = wait_child(child, false) {
+                        self.handle(idx, event);
+                    }
+                }
+                Process::Thread(handle) if handle.is_finished() => {
+                    if let Process::Thread(handle) = std::mem::replace(&mut self.processes[idx], Process::Done(ExitStatus::FAILURE)) {
+                        self.processes[idx] = Process::Done(handle.join().unwrap_or(ExitStatus::FAILURE));
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.check_done();
+    }
+
+    /// Lets a stopped job continue.
+    pub fn resume(&mut self) {
+        #[cfg(unix)]
+        match self.pgid {
+            Some(pgid) => unsafe {
+                libc::kill(-(pgid as libc::pid_t), libc::SIGCONT);
+            },
+            None => {
+                for process in &self.processes {
+                    if let Process::External { child, status: None } = process {
+                        unsafe {
+                            libc::kill(child.id() as libc::pid_t, libc::SIGCONT);
+                        }
+                    }
+                }
+            }
+        }
+        self.set_state(JobState::Running);
+        self.changed = false;
+    }
+
+    /// Describes the signal that killed the job, like `Segmentation fault (core dumped)`.
+    fn signal_message(&self) -> String {
+        match self.signal {
+            Some((signal, core_dumped)) => {
+                let message = crate::signals::describe(signal);
+                if core_dumped {
+                    format!("{} (core dumped)", message)
+                } else {
+                    message
+                }
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Formats the job like `jobs` does, e.g. `[1]+  Running                 sleep 10 &`.
+    fn format(&self, marker: char) -> String {
+        let state = match self.state {
+            JobState::Running => String::from("Running"),
+            JobState::Stopped => String::from("Stopped"),
+            JobState::Done(_) if self.signal.is_some() => self.signal_message(),
+            JobState::Done(status) if status.success() => String::from("Done"),
+            JobState::Done(status) => format!("Exit {}", status.code),
+        };
+        let suffix = if self.state == JobState::Running { " &" } else { "" };
+        format!("[{}]{}  {:<24}{}{}", self.id, marker, state, self.command, suffix)
+    }
+}
+
+/// The exit status of a job that was stopped, like for a command killed by SIGTSTP.
+#[cfg(unix)]
+const STOPPED: ExitStatus = ExitStatus {
+    code: 128 + libc::SIGTSTP,
+};
+#[cfg(not(unix))]
+const STOPPED: ExitStatus = ExitStatus::FAILURE;
+
+/// Waits for a job that runs in the foreground, returning its exit status.
+/// If it gets stopped, it is added to the jobs, so it can be continued with `fg` or `bg`.
+pub fn wait_foreground(shell_state: &mut ShellState, mut job: Job) -> ExitStatus {
+    let state = job.wait();
+    if shell_state.job_control {
+        set_foreground(None);
+    }
+    match state {
+        JobState::Stopped => {
+            shell_state.jobs.add(job);
+            eprint!("\n{}", shell_state.jobs.report());
+            STOPPED
+        }
+        _ => {
+            match job.signal {
+                // The shell doesn't get Ctrl-C itself when the command has the terminal,
+                // but a loop that ran the command should stop as well.
+                Some((signal, _)) if crate::signals::is_interrupt(signal) => {
+                    crate::signals::interrupt();
+                    // Ctrl-C was echoed without a newline, so the prompt would start right after it.
+                    if shell_state.job_control {
+                        eprintln!();
+                    }
+                }
+                // A closed pipe isn't an error worth reporting.
+                Some((signal, _)) if crate::signals::is_broken_pipe(signal) => (),
+                Some(_) => eprintln!("{}", job.signal_message()),
+                None => (),
+            }
+            job.status()
+        }
+    }
+}
+
+/// The jobs of the shell, in the order they were started.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+    /// Whether `exit` already warned that there are jobs left.
+    warned: bool,
+}
+
+/// A copy of the shell, e.g. for a command in a pipeline, doesn't own the jobs of the original.
+impl Clone for Jobs {
+    fn clone(&self) -> Jobs {
+        Jobs::default()
+    }
+}
+
+impl Jobs {
+    /// Adds a job, returning its number.
+    /// A new job gets the next free number, one that was added before keeps its own.
+    pub fn add(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        }
+        let id = job.id;
+        self.jobs.push(job);
+        id
+    }
+
+    /// `+` for the current job, which is the latest one, `-` for the one before it.
+    fn marker(&self, idx: usize) -> char {
+        match self.jobs.len() - idx {
+            1 => '+',
+            2 => '-',
+            _ => ' ',
+        }
+    }
+
+    /// Finds a job by a job spec like `%1`, `%+`, `%-`, or by the process ID of one of its commands.
+    /// Without a spec, the current job is used.
+    fn find(&self, spec: Option<&str>) -> Result<usize, String> {
+        let spec = spec.unwrap_or("%+");
+        let idx = match spec.strip_prefix('%') {
+            Some("" | "+" | "%") => self.jobs.len().checked_sub(1),
+            Some("-") => self.jobs.len().checked_sub(2),
+            Some(id) => id.parse().ok().and_then(|id: usize| self.jobs.iter().position(|job| job.id == id)),
+            None => spec.parse().ok().and_then(|pid: u32| {
+                self.jobs.iter().position(|job| {
+                    job.processes
+                        .iter()
+                        .any(|process| matches!(process, Process::External { child, .. } if child.id() == pid))
+                })
+            }),
+        };
+        idx.ok_or_else(|| format!("{}: no such job", spec))
+    }
+
+    pub fn get_mut(&mut self, spec: Option<&str>) -> Result<&mut Job, String> {
+        let idx = self.find(spec)?;
+        Ok(&mut self.jobs[idx])
+    }
+
+    pub fn remove(&mut self, spec: Option<&str>) -> Result<Job, String> {
+        let idx = self.find(spec)?;
+        Ok(self.jobs.remove(idx))
+    }
+
+    /// Removes every job.
+    pub fn take_all(&mut self) -> Vec<Job> {
+        std::mem::take(&mut self.jobs)
+    }
+
+    /// Returns a warning if there are jobs that didn't finish yet,
+    /// but only the first time, so the shell exits if `exit` is used again.
+    pub fn exit_warning(&mut self) -> Option<&'static str> {
+        if self.warned {
+            return None;
+        }
+        self.update();
+        let warning = if self.jobs.iter().any(|job| job.state == JobState::Stopped) {
+            "there are stopped jobs"
+        } else if self.jobs.iter().any(|job| job.state == JobState::Running) {
+            "there are running jobs"
+        } else {
+            return None;
+        };
+        self.warned = true;
+        Some(warning)
+    }
+
+    /// Checks every job for state changes.
+    pub fn update(&mut self) {
+        for job in &mut self.jobs {
+            job.poll();
+        }
+    }
+
+    /// Returns a line for every job that changed its state since the last report,
+    /// and forgets about the jobs that are done.
+    pub fn report(&mut self) -> String {
+        let mut output = String::new();
+        for idx in 0..self.jobs.len() {
+            if self.jobs[idx].changed {
+                output.push_str(&self.jobs[idx].format(self.marker(idx)));
+                output.push('\n');
+                self.jobs[idx].changed = false;
+            }
+        }
+        self.jobs.retain(|job| !matches!(job.state, JobState::Done(_)));
+        output
+    }
+
+    /// Lists every job, like `jobs`.
+    pub fn list(&mut self) -> String {
+        let mut output = String::new();
+        for idx in 0..self.jobs.len() {
+            output.push_str(&self.jobs[idx].format(self.marker(idx)));
+            output.push('\n');
+            self.jobs[idx].changed = false;
+        }
+        self.jobs.retain(|job| !matches!(job.state, JobState::Done(_)));
+        output
+    }
+}
+
+/// The shell's file descriptor for the terminal, or -1 if job control is off.
+#[cfg(unix)]
+static TERMINAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(-1);
+
+/// Turns job control on if stdin is a terminal, returning whether it is on.
+/// The shell gets its own process group and the terminal, and ignores
+/// the signals for stopping it, so only the foreground job gets stopped by Ctrl-Z.
+#[cfg(unix)]
+pub fn init_job_control() -> bool {
+    use std::io::IsTerminal;
+    use std::sync::atomic::Ordering;
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    unsafe {
+        // If the shell was started in the background, wait until it is in the foreground.
+        loop {
+            let pgrp = libc::getpgrp();
+            if libc::tcgetpgrp(libc::STDIN_FILENO) == pgrp {
+                break;
+            }
+            libc::kill(-pgrp, libc::SIGTTIN);
+        }
+        for signal in [libc::SIGTSTP, libc::SIGTTIN, libc::SIGTTOU] {
+            libc::signal(signal, libc::SIG_IGN);
+        }
+        let pid = libc::getpid();
+        libc::setpgid(pid, pid);
+        let terminal = libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 10);
+        if terminal == -1 {
+            return false;
+        }
+        libc::tcsetpgrp(terminal, pid);
+        TERMINAL.store(terminal, Ordering::Relaxed);
+    }
+    true
+}
+
+#[cfg(not(unix))]
+pub fn init_job_control() -> bool {
+    false
+}
+
+/// Gives the terminal to a process group, or back to the shell if `pgid` is `None`.
+pub fn set_foreground(pgid: Option<u32>) {
+    #[cfg(unix)]
+    {
+        let terminal = TERMINAL.load(std::sync::atomic::Ordering::Relaxed);
+        if terminal != -1 {
+            let pgid = pgid.map_or_else(|| unsafe { libc::getpgrp() }, |pgid| pgid as libc::pid_t);
+            unsafe {
+                libc::tcsetpgrp(terminal, pgid);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = pgid;
+}
+
+/// Makes an external command join the process group `pgid`, or start a new one if it is `None`.
+/// A foreground command also takes the terminal.
+pub fn prepare_child(command: &mut std::process::Command, pgid: Option<u32>, foreground: bool) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let terminal = TERMINAL.load(std::sync::atomic::Ordering::Relaxed);
+        let pgid = pgid.unwrap_or(0) as libc::pid_t;
+        // Safety: only async-signal-safe functions are called between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                libc::setpgid(0, pgid);
+                if foreground && terminal != -1 {
+                    libc::tcsetpgrp(terminal, libc::getpgrp());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (command, pgid, foreground);
+}
+
+/// Puts a child that was started with `prepare_child` into its process group from the
+/// shell's side as well, since either one could run first. Returns the process group.
+pub fn join_group(child: &Child, pgid: Option<u32>) -> u32 {
+    let pgid = pgid.unwrap_or_else(|| child.id());
+    #[cfg(unix)]
+    unsafe {
+        libc::setpgid(child.id() as libc::pid_t, pgid as libc::pid_t);
+    }
+    pgid
+}
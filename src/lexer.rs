@@ -0,0 +1,872 @@
+use crate::parser::{parse, ParseError, Program};
+use crate::variables::is_valid_name;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// A byte range in the input that a token was lexed from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+/// How `${var/pattern/replacement}` replaces matches.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReplaceMode {
+    /// `${var/pattern/replacement}`, replaces the first match.
+    First,
+    /// `${var//pattern/replacement}`, replaces every match.
+    All,
+    /// `${var/#pattern/replacement}`, replaces a match at the start.
+    Prefix,
+    /// `${var/%pattern/replacement}`, replaces a match at the end.
+    Suffix,
+}
+
+/// The operators of a parameter expansion. For the ones with a `colon`,
+/// variables that are set but empty are treated like unset ones when it is `true`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamOp {
+    /// `${#var}`
+    Length,
+    /// `${var:-default}`
+    Default { colon: bool, word: Word },
+    /// `${var:=default}`
+    Assign { colon: bool, word: Word },
+    /// `${var:?message}`
+    Error { colon: bool, word: Word },
+    /// `${var:+alternative}`
+    Alternative { colon: bool, word: Word },
+    /// `${var#pattern}` and `${var##pattern}`
+    RemovePrefix { longest: bool, pattern: Word },
+    /// `${var%pattern}` and `${var%%pattern}`
+    RemoveSuffix { longest: bool, pattern: Word },
+    /// `${var/pattern/replacement}`
+    Replace {
+        mode: ReplaceMode,
+        pattern: Word,
+        replacement: Word,
+    },
+}
+
+/// A parameter expansion, like `$HOME`, `${HOME}` or `${HOME:-/root}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    /// The name of a variable, or a special parameter like `?`.
+    pub name: String,
+    pub op: Option<ParamOp>,
+}
+
+/// A piece of a word. Words can be made of multiple parts,
+/// e.g. `foo"bar baz"` is a literal followed by a quoted part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Unquoted text.
+    Literal(String),
+    /// Text that was inside of single quotes, or escaped with a backslash, taken as-is.
+    Quoted(String),
+    /// Everything inside of double quotes. Only `Quoted` text and expansions can be in here,
+    /// whose results aren't split into fields or used as patterns.
+    DoubleQuoted(Vec<WordPart>),
+    Param(Parameter),
+    /// A command substitution, `$(command)` or `` `command` ``.
+    Command(Program),
+    /// An arithmetic expansion, `$((expression))`. The expression is expanded before it is evaluated.
+    Arithmetic(Word),
+}
+
+/// A single word, like a command name, an argument or the target of a redirection.
+/// The parts are expanded and joined before running a command.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+/// Control operators, which separate commands from each other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operator {
+    /// `|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `&`, which runs the list before it in the background.
+    Background,
+    /// `;`
+    Semicolon,
+    /// `;;`, which ends an arm of a `case`.
+    DoubleSemicolon,
+    /// `(`
+    LeftParen,
+    /// `)`
+    RightParen,
+}
+
+/// Redirection operators.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RedirectOp {
+    /// `<`
+    Input,
+    /// `<>`
+    ReadWrite,
+    /// `>`
+    Overwrite,
+    /// `>|`, overwrite even if `noclobber` is set.
+    Clobber,
+    /// `>>`
+    Append,
+    /// `<&`
+    DupInput,
+    /// `>&`
+    DupOutput,
+    /// `&>`, redirects both stdout and stderr.
+    OutputAndError,
+    /// `&>>`
+    AppendOutputAndError,
+}
+
+impl RedirectOp {
+    /// The file descriptor that is redirected if none was given, e.g. `1` for `>`.
+    pub fn default_fd(self) -> u32 {
+        match self {
+            RedirectOp::Input | RedirectOp::ReadWrite | RedirectOp::DupInput => 0,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Word(Word),
+    Operator(Operator),
+    /// A redirection operator, with the file descriptor in front of it, like the `2` in `2>`.
+    Redirect(Option<u32>, RedirectOp),
+    /// An unquoted `!` on its own, which inverts the exit status of a pipeline when it is
+    /// in front of it, and is an ordinary word everywhere else.
+    Bang,
+    Newline,
+}
+
+/// A token and where in the input it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Helper for building up a word while lexing.
+#[derive(Default)]
+struct WordBuilder {
+    parts: Vec<WordPart>,
+    literal: String,
+    start: Option<usize>,
+}
+
+impl WordBuilder {
+    fn begin(&mut self, idx: usize) {
+        if self.start.is_none() {
+            self.start = Some(idx);
+        }
+    }
+
+    fn push_literal(&mut self, idx: usize, character: char) {
+        self.begin(idx);
+        self.literal.push(character);
+    }
+
+    fn push_part(&mut self, idx: usize, part: WordPart) {
+        self.begin(idx);
+        self.flush_literal();
+        self.parts.push(part);
+    }
+
+    /// If the word so far is only made of digits, like the `2` in `2>file`,
+    /// it is taken as the file descriptor of a redirection.
+    fn take_fd(&mut self) -> Option<(usize, u32)> {
+        if !self.parts.is_empty() || self.literal.is_empty() || !self.literal.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let fd = self.literal.parse::<u32>().ok()?;
+        self.literal.clear();
+        self.start.take().map(|start| (start, fd))
+    }
+
+    fn flush_literal(&mut self) {
+        if !self.literal.is_empty() {
+            self.parts.push(WordPart::Literal(std::mem::take(&mut self.literal)));
+        }
+    }
+
+    fn into_word(mut self) -> Word {
+        self.flush_literal();
+        Word { parts: self.parts }
+    }
+
+    /// Pushes the finished word (if there is one) to `tokens`.
+    fn finish(&mut self, end: usize, tokens: &mut Vec<Token>) {
+        self.flush_literal();
+        if let Some(start) = self.start.take() {
+            let parts = std::mem::take(&mut self.parts);
+            let kind = match parts.as_slice() {
+                [WordPart::Literal(text)] if text == "!" => TokenKind::Bang,
+                _ => TokenKind::Word(Word { parts }),
+            };
+            tokens.push(Token {
+                kind,
+                span: Span::new(start, end),
+            });
+        }
+    }
+}
+
+/// The error for a quote without a matching closing quote.
+fn unterminated_quote(quote: char, start: usize) -> ParseError {
+    ParseError {
+        message: format!("unterminated quote, missing closing `{}`", quote),
+        span: Span::new(start, start + 1),
+        incomplete: true,
+        hint: Some(format!(
+            "unterminated {} quote started here",
+            if quote == '"' { "double" } else { "single" }
+        )),
+    }
+}
+
+/// Lexes a single quoted part of a word after the `'`. Everything up until the next `'` is taken as-is.
+fn lex_single_quoted(chars: &mut Peekable<CharIndices>, start: usize) -> Result<String, ParseError> {
+    let mut quoted = String::new();
+    for (_, c) in chars.by_ref() {
+        if c == '\'' {
+            return Ok(quoted);
+        }
+        quoted.push(c);
+    }
+    Err(unterminated_quote('\'', start))
+}
+
+/// Lexes a double quoted part of a word after the `"`, up until the closing `"`.
+/// Expansions still work inside of double quotes, and a `\` only escapes `$`, `` ` ``, `"`,
+/// `\` and newlines.
+fn lex_double_quoted(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Vec<WordPart>, ParseError> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    while let Some((idx, character)) = chars.next() {
+        let part = match character {
+            '"' => {
+                if !text.is_empty() {
+                    parts.push(WordPart::Quoted(text));
+                }
+                return Ok(parts);
+            }
+            '\\' => {
+                match chars.next_if(|(_, c)| matches!(c, '$' | '`' | '"' | '\\' | '\n')) {
+                    // A backslash before a newline continues the line.
+                    Some((_, '\n')) => (),
+                    Some((_, c)) => text.push(c),
+                    None => text.push(character),
+                }
+                continue;
+            }
+            '$' => match lex_dollar(chars, idx)? {
+                Some(part) => part,
+                None => {
+                    text.push(character);
+                    continue;
+                }
+            },
+            '`' => WordPart::Command(lex_backticks(chars, idx)?),
+            _ => {
+                text.push(character);
+                continue;
+            }
+        };
+        if !text.is_empty() {
+            parts.push(WordPart::Quoted(std::mem::take(&mut text)));
+        }
+        parts.push(part);
+    }
+    Err(unterminated_quote('"', start))
+}
+
+/// Lexes the characters that mean the same everywhere in a word: quotes, backslashes,
+/// expansions and plain characters. The callers handle the characters that end a word themselves.
+fn lex_word_char(
+    chars: &mut Peekable<CharIndices>,
+    idx: usize,
+    character: char,
+    word: &mut WordBuilder,
+) -> Result<(), ParseError> {
+    match character {
+        '\'' => word.push_part(idx, WordPart::Quoted(lex_single_quoted(chars, idx)?)),
+        '"' => word.push_part(idx, WordPart::DoubleQuoted(lex_double_quoted(chars, idx)?)),
+        '\\' => match chars.next() {
+            // A backslash before a newline continues the line.
+            Some((_, '\n')) => (),
+            Some((_, c)) => word.push_part(idx, WordPart::Quoted(c.to_string())),
+            // A backslash at the very end is taken literally.
+            None => word.push_literal(idx, character),
+        },
+        '$' => match lex_dollar(chars, idx)? {
+            Some(part) => word.push_part(idx, part),
+            None => word.push_literal(idx, character),
+        },
+        '`' => word.push_part(idx, WordPart::Command(lex_backticks(chars, idx)?)),
+        _ => word.push_literal(idx, character),
+    }
+    Ok(())
+}
+
+/// Parses the text of a command substitution, which starts at `offset` in the input.
+fn parse_substitution(text: &str, offset: usize) -> Result<Program, ParseError> {
+    parse(text).map_err(|err| ParseError {
+        span: Span::new(err.span.start + offset, err.span.end + offset),
+        ..err
+    })
+}
+
+/// Lexes a `$(command)` after the `$(`, up until the matching `)`.
+/// Parentheses inside of quotes, backticks or nested substitutions don't count.
+fn lex_command_substitution(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Program, ParseError> {
+    let mut text = String::new();
+    let mut depth = 0;
+    let missing_paren = || ParseError {
+        message: String::from("missing `)` for command substitution"),
+        span: Span::new(start, start + 2),
+        incomplete: true,
+        hint: Some(String::from("command substitution started here")),
+    };
+    loop {
+        let (_, character) = chars.next().ok_or_else(missing_paren)?;
+        match character {
+            '(' => depth += 1,
+            ')' if depth == 0 => break,
+            ')' => depth -= 1,
+            '"' | '\'' | '`' => {
+                text.push(character);
+                // Copy everything up to the closing quote or backtick.
+                loop {
+                    let (_, c) = chars.next().ok_or_else(missing_paren)?;
+                    text.push(c);
+                    if c == character {
+                        break;
+                    }
+                    if c == '\\' && character != '\'' {
+                        text.extend(chars.next().map(|(_, c)| c));
+                    }
+                }
+                continue;
+            }
+            '\\' => {
+                text.push(character);
+                if let Some((_, c)) = chars.next() {
+                    text.push(c);
+                }
+                continue;
+            }
+            _ => (),
+        }
+        text.push(character);
+    }
+    parse_substitution(&text, start + 2)
+}
+
+/// Lexes a `` `command` `` after the first backtick, up until the closing one.
+/// Inside of backticks, a `\` only escapes `$`, `` ` `` and `\`.
+fn lex_backticks(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Program, ParseError> {
+    let mut text = String::new();
+    loop {
+        let (_, character) = chars.next().ok_or_else(|| ParseError {
+            message: String::from("missing closing backtick for command substitution"),
+            span: Span::new(start, start + 1),
+            incomplete: true,
+            hint: Some(String::from("command substitution started here")),
+        })?;
+        match character {
+            '`' => break,
+            '\\' => match chars.next_if(|(_, c)| matches!(c, '$' | '`' | '\\')) {
+                Some((_, c)) => text.push(c),
+                None => text.push(character),
+            },
+            _ => text.push(character),
+        }
+    }
+    parse_substitution(&text, start + 1)
+}
+
+/// Lexes a `$((expression))` after the `$((`, up until the matching `))`.
+fn lex_arithmetic(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Word, ParseError> {
+    let missing_parens = || ParseError {
+        message: String::from("missing `))` for arithmetic expansion"),
+        span: Span::new(start, start + 3),
+        incomplete: true,
+        hint: Some(String::from("arithmetic expansion started here")),
+    };
+    let mut word = WordBuilder::default();
+    let mut depth = 0;
+    while let Some((idx, character)) = chars.next() {
+        match character {
+            ')' if depth == 0 => {
+                return match chars.next() {
+                    Some((_, ')')) => Ok(word.into_word()),
+                    _ => Err(missing_parens()),
+                };
+            }
+            '(' | ')' => {
+                depth += if character == '(' { 1 } else { -1 };
+                word.push_literal(idx, character);
+            }
+            _ => lex_word_char(chars, idx, character, &mut word)?,
+        }
+    }
+    Err(missing_parens())
+}
+
+/// Lexes whatever follows a `$`: an arithmetic expansion, a command substitution or a parameter.
+/// Returns `None` if there is neither, in which case the `$` is taken literally.
+fn lex_dollar(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Option<WordPart>, ParseError> {
+    if chars.next_if(|(_, c)| *c == '(').is_some() {
+        if chars.next_if(|(_, c)| *c == '(').is_some() {
+            return lex_arithmetic(chars, start).map(|word| Some(WordPart::Arithmetic(word)));
+        }
+        return lex_command_substitution(chars, start).map(|program| Some(WordPart::Command(program)));
+    }
+    Ok(lex_parameter(chars, start)?.map(WordPart::Param))
+}
+
+/// The error for a `${` without a matching `}`.
+fn missing_brace(start: usize) -> ParseError {
+    ParseError {
+        message: String::from("missing `}` for parameter expansion"),
+        span: Span::new(start, start + 2),
+        incomplete: true,
+        hint: Some(String::from("parameter expansion started here")),
+    }
+}
+
+/// Lexes the word inside of a parameter expansion, like the `default` in `${var:-default}`,
+/// up until one of `terminators`, which is consumed and returned as well.
+fn lex_inner_word(
+    chars: &mut Peekable<CharIndices>,
+    start: usize,
+    terminators: &[char],
+) -> Result<(Word, char), ParseError> {
+    let mut word = WordBuilder::default();
+    while let Some((idx, character)) = chars.next() {
+        match character {
+            c if terminators.contains(&c) => return Ok((word.into_word(), c)),
+            _ => lex_word_char(chars, idx, character, &mut word)?,
+        }
+    }
+    Err(missing_brace(start))
+}
+
+/// Lexes everything between `${` and `}`.
+fn lex_braced_parameter(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Parameter, ParseError> {
+    let bad_substitution = |chars: &mut Peekable<CharIndices>| {
+        let end = chars.peek().map_or(start + 2, |(idx, _)| *idx + 1);
+        ParseError {
+            message: String::from("bad substitution"),
+            span: Span::new(start, end),
+            incomplete: false,
+            hint: None,
+        }
+    };
+    // `${#var}` is the length of `var`, but `${#}` is the number of positional parameters.
+    let mut length = chars.next_if(|(_, c)| *c == '#').is_some();
+    let mut name = String::new();
+    match chars.peek().map(|(_, c)| *c) {
+        Some('}') if length => {
+            length = false;
+            name.push('#');
+        }
+        // Positional parameters can have more than one digit in braces, like `${10}`.
+        Some(c) if c.is_ascii_digit() => {
+            while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                name.push(c);
+            }
+        }
+        Some(c) if is_special_parameter(&c.to_string()) => {
+            chars.next();
+            name.push(c);
+        }
+        _ => {
+            while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+        }
+    }
+    if !is_valid_name(&name) && !is_special_parameter(&name) {
+        return Err(bad_substitution(chars));
+    }
+    let (_, character) = chars.next().ok_or_else(|| missing_brace(start))?;
+    let op = match character {
+        '}' if length => Some(ParamOp::Length),
+        '}' => None,
+        _ if length => return Err(bad_substitution(chars)),
+        ':' | '-' | '=' | '?' | '+' => {
+            let colon = character == ':';
+            let op_char = if colon {
+                chars.next().ok_or_else(|| missing_brace(start))?.1
+            } else {
+                character
+            };
+            if !matches!(op_char, '-' | '=' | '?' | '+') {
+                return Err(bad_substitution(chars));
+            }
+            let (word, _) = lex_inner_word(chars, start, &['}'])?;
+            Some(match op_char {
+                '-' => ParamOp::Default { colon, word },
+                '=' => ParamOp::Assign { colon, word },
+                '?' => ParamOp::Error { colon, word },
+                _ => ParamOp::Alternative { colon, word },
+            })
+        }
+        '#' | '%' => {
+            let longest = chars.next_if(|(_, c)| *c == character).is_some();
+            let (pattern, _) = lex_inner_word(chars, start, &['}'])?;
+            Some(if character == '#' {
+                ParamOp::RemovePrefix { longest, pattern }
+            } else {
+                ParamOp::RemoveSuffix { longest, pattern }
+            })
+        }
+        '/' => {
+            let mode = match chars.peek().map(|(_, c)| *c) {
+                Some('/') => ReplaceMode::All,
+                Some('#') => ReplaceMode::Prefix,
+                Some('%') => ReplaceMode::Suffix,
+                _ => ReplaceMode::First,
+            };
+            if mode != ReplaceMode::First {
+                chars.next();
+            }
+            let (pattern, terminator) = lex_inner_word(chars, start, &['/', '}'])?;
+            let replacement = if terminator == '/' {
+                lex_inner_word(chars, start, &['}'])?.0
+            } else {
+                Word::default()
+            };
+            Some(ParamOp::Replace {
+                mode,
+                pattern,
+                replacement,
+            })
+        }
+        _ => return Err(bad_substitution(chars)),
+    };
+    Ok(Parameter { name, op })
+}
+
+/// Lexes the parameter after a `$`, returning `None` if there is none,
+/// in which case the `$` is taken literally.
+fn lex_parameter(chars: &mut Peekable<CharIndices>, start: usize) -> Result<Option<Parameter>, ParseError> {
+    let name = match chars.peek().map(|(_, c)| *c) {
+        Some('{') => {
+            chars.next();
+            return lex_braced_parameter(chars, start).map(Some);
+        }
+        Some(c) if is_special_parameter(&c.to_string()) => {
+            chars.next();
+            c.to_string()
+        }
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+            name
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(Parameter { name, op: None }))
+}
+
+/// Checks if `name` is a special parameter, like `?` for the last exit status,
+/// or a positional parameter like `1`.
+fn is_special_parameter(name: &str) -> bool {
+    matches!(name, "?" | "$" | "#" | "@" | "*") || (!name.is_empty() && name.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Splits the input into words, operators, redirections and newlines.
+/// Operators are only recognized outside of quotes and without a backslash in front of them,
+/// so `echo "a > b"` is a command with a single argument, and `echo a \> b` has no redirection.
+pub fn lex(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut word = WordBuilder::default();
+    let mut chars = input.char_indices().peekable();
+    while let Some((idx, character)) = chars.next() {
+        match character {
+            // A `#` at the start of a word starts a comment, which goes until the end of the line.
+            '#' if word.start.is_none() => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            ' ' | '\t' => word.finish(idx, &mut tokens),
+            '\n' => {
+                word.finish(idx, &mut tokens);
+                tokens.push(Token {
+                    kind: TokenKind::Newline,
+                    span: Span::new(idx, idx + 1),
+                });
+            }
+            '|' => {
+                word.finish(idx, &mut tokens);
+                let (op, len) = if chars.next_if(|(_, c)| *c == '|').is_some() {
+                    (Operator::Or, 2)
+                } else {
+                    (Operator::Pipe, 1)
+                };
+                tokens.push(Token {
+                    kind: TokenKind::Operator(op),
+                    span: Span::new(idx, idx + len),
+                });
+            }
+            '&' if chars.peek().map(|(_, c)| *c) == Some('>') => {
+                word.finish(idx, &mut tokens);
+                chars.next();
+                let (op, len) = if chars.next_if(|(_, c)| *c == '>').is_some() {
+                    (RedirectOp::AppendOutputAndError, 3)
+                } else {
+                    (RedirectOp::OutputAndError, 2)
+                };
+                tokens.push(Token {
+                    kind: TokenKind::Redirect(None, op),
+                    span: Span::new(idx, idx + len),
+                });
+            }
+            '&' => {
+                word.finish(idx, &mut tokens);
+                let (op, len) = if chars.next_if(|(_, c)| *c == '&').is_some() {
+                    (Operator::And, 2)
+                } else {
+                    (Operator::Background, 1)
+                };
+                tokens.push(Token {
+                    kind: TokenKind::Operator(op),
+                    span: Span::new(idx, idx + len),
+                });
+            }
+            ';' => {
+                word.finish(idx, &mut tokens);
+                let (op, len) = if chars.next_if(|(_, c)| *c == ';').is_some() {
+                    (Operator::DoubleSemicolon, 2)
+                } else {
+                    (Operator::Semicolon, 1)
+                };
+                tokens.push(Token {
+                    kind: TokenKind::Operator(op),
+                    span: Span::new(idx, idx + len),
+                });
+            }
+            '(' | ')' => {
+                word.finish(idx, &mut tokens);
+                let op = if character == '(' { Operator::LeftParen } else { Operator::RightParen };
+                tokens.push(Token {
+                    kind: TokenKind::Operator(op),
+                    span: Span::new(idx, idx + 1),
+                });
+            }
+            '<' | '>' => {
+                let (start, fd) = match word.take_fd() {
+                    Some((start, fd)) => (start, Some(fd)),
+                    None => (idx, None),
+                };
+                word.finish(idx, &mut tokens);
+                // `>` and `<` are prefixes of the other operators, so check those first.
+                let next = chars.peek().map(|(_, c)| *c);
+                let op = match (character, next) {
+                    ('<', Some('>')) => RedirectOp::ReadWrite,
+                    ('<', Some('&')) => RedirectOp::DupInput,
+                    ('<', _) => RedirectOp::Input,
+                    ('>', Some('>')) => RedirectOp::Append,
+                    ('>', Some('|')) => RedirectOp::Clobber,
+                    ('>', Some('&')) => RedirectOp::DupOutput,
+                    _ => RedirectOp::Overwrite,
+                };
+                let mut end = idx + 1;
+                if !matches!(op, RedirectOp::Input | RedirectOp::Overwrite) {
+                    chars.next();
+                    end += 1;
+                }
+                tokens.push(Token {
+                    kind: TokenKind::Redirect(fd, op),
+                    span: Span::new(start, end),
+                });
+            }
+            _ => lex_word_char(&mut chars, idx, character, &mut word)?,
+        }
+    }
+    word.finish(input.len(), &mut tokens);
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<TokenKind> {
+        lex(input).unwrap().into_iter().map(|token| token.kind).collect()
+    }
+
+    fn word(parts: Vec<WordPart>) -> TokenKind {
+        TokenKind::Word(Word { parts })
+    }
+
+    fn literal(text: &str) -> WordPart {
+        WordPart::Literal(text.to_string())
+    }
+
+    fn quoted(text: &str) -> WordPart {
+        WordPart::Quoted(text.to_string())
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(kinds("a|b>c >> d\ne"), vec![
+            word(vec![literal("a")]),
+            TokenKind::Operator(Operator::Pipe),
+            word(vec![literal("b")]),
+            TokenKind::Redirect(None, RedirectOp::Overwrite),
+            word(vec![literal("c")]),
+            TokenKind::Redirect(None, RedirectOp::Append),
+            word(vec![literal("d")]),
+            TokenKind::Newline,
+            word(vec![literal("e")]),
+        ]);
+    }
+
+    #[test]
+    fn operators_in_quotes() {
+        assert_eq!(kinds("echo \"a > b\" 'c|d'e"), vec![
+            word(vec![literal("echo")]),
+            word(vec![WordPart::DoubleQuoted(vec![quoted("a > b")])]),
+            word(vec![quoted("c|d"), literal("e")]),
+        ]);
+    }
+
+    #[test]
+    fn list_operators() {
+        assert_eq!(kinds("a&&b||c;d"), vec![
+            word(vec![literal("a")]),
+            TokenKind::Operator(Operator::And),
+            word(vec![literal("b")]),
+            TokenKind::Operator(Operator::Or),
+            word(vec![literal("c")]),
+            TokenKind::Operator(Operator::Semicolon),
+            word(vec![literal("d")]),
+        ]);
+    }
+
+    #[test]
+    fn redirections() {
+        assert_eq!(kinds("2>&1 &> f &>> g <> h >| i 3< j <&0"), vec![
+            TokenKind::Redirect(Some(2), RedirectOp::DupOutput),
+            word(vec![literal("1")]),
+            TokenKind::Redirect(None, RedirectOp::OutputAndError),
+            word(vec![literal("f")]),
+            TokenKind::Redirect(None, RedirectOp::AppendOutputAndError),
+            word(vec![literal("g")]),
+            TokenKind::Redirect(None, RedirectOp::ReadWrite),
+            word(vec![literal("h")]),
+            TokenKind::Redirect(None, RedirectOp::Clobber),
+            word(vec![literal("i")]),
+            TokenKind::Redirect(Some(3), RedirectOp::Input),
+            word(vec![literal("j")]),
+            TokenKind::Redirect(None, RedirectOp::DupInput),
+            word(vec![literal("0")]),
+        ]);
+        // Only digits right in front of the operator are a file descriptor.
+        assert_eq!(kinds("a2>f"), vec![
+            word(vec![literal("a2")]),
+            TokenKind::Redirect(None, RedirectOp::Overwrite),
+            word(vec![literal("f")]),
+        ]);
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(kinds(r#"a"b $x"'c d'"#), vec![word(vec![
+            literal("a"),
+            WordPart::DoubleQuoted(vec![quoted("b "), WordPart::Param(Parameter {
+                name: String::from("x"),
+                op: None,
+            })]),
+            quoted("c d"),
+        ])]);
+        assert_eq!(kinds(r#"'a "b"' "a 'b'""#), vec![word(vec![quoted("a \"b\"")]), word(vec![
+            WordPart::DoubleQuoted(vec![quoted("a 'b'")])
+        ])]);
+        assert_eq!(kinds("''"), vec![word(vec![quoted("")])]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(kinds(r"a\ b \$x \>"), vec![
+            word(vec![literal("a"), quoted(" "), literal("b")]),
+            word(vec![quoted("$"), literal("x")]),
+            word(vec![quoted(">")]),
+        ]);
+        assert_eq!(kinds(r#""a\"b\$c\d""#), vec![word(vec![WordPart::DoubleQuoted(vec![quoted(r#"a"b$c\d"#)])])]);
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(kinds("a # b c\nd#e"), vec![
+            word(vec![literal("a")]),
+            TokenKind::Newline,
+            word(vec![literal("d#e")]),
+        ]);
+    }
+
+    #[test]
+    fn case_operators() {
+        assert_eq!(kinds("a);;\n(b)"), vec![
+            word(vec![literal("a")]),
+            TokenKind::Operator(Operator::RightParen),
+            TokenKind::Operator(Operator::DoubleSemicolon),
+            TokenKind::Newline,
+            TokenKind::Operator(Operator::LeftParen),
+            word(vec![literal("b")]),
+            TokenKind::Operator(Operator::RightParen),
+        ]);
+    }
+
+    #[test]
+    fn spans() {
+        let spans: Vec<Span> = lex("echo  hi>out 2>&1").unwrap().into_iter().map(|token| token.span).collect();
+        assert_eq!(spans, vec![
+            Span::new(0, 4),
+            Span::new(6, 8),
+            Span::new(8, 9),
+            Span::new(9, 12),
+            Span::new(13, 16),
+            Span::new(16, 17),
+        ]);
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        let err = lex("echo \"abc").unwrap_err();
+        assert!(err.incomplete);
+        assert_eq!(err.span.start, 5);
+        assert_eq!(err.hint.as_deref(), Some("unterminated double quote started here"));
+        assert!(lex("echo 'abc").unwrap_err().incomplete);
+    }
+
+    #[test]
+    fn bang() {
+        assert_eq!(kinds("! a !b '!' !"), vec![
+            TokenKind::Bang,
+            word(vec![literal("a")]),
+            word(vec![literal("!b")]),
+            word(vec![quoted("!")]),
+            TokenKind::Bang,
+        ]);
+    }
+}
@@ -1,42 +1,157 @@
+mod arithmetic;
+mod ast_json;
+mod braces;
 mod builtins;
+mod cli;
 mod commands;
+#[cfg(feature = "readline")]
+mod completion;
+mod error;
+mod executor;
+mod expansion;
+mod glob;
+mod jobs;
+mod lexer;
+mod options;
+mod parser;
 mod prompt;
 mod shared_functions;
+mod signals;
+mod traps;
+mod streams;
+mod variables;
 
 #[cfg(feature = "readline")]
 use rustyline::Editor;
-use shared_functions::{process_input, run_loop, ShellState};
+use cli::{Action, Cli, Source};
+use error::ShellError;
+use parser::{check, parse};
+use shared_functions::{exit_shell, init_interactive, process_script, run_loop, ShellState};
+use std::io::{IsTerminal, Read};
+
+/// Runs a whole script, and then exits with the status of its last command, or the one given to `exit`.
+/// Syntax errors show `origin` in front of their line and column.
+fn run_script(shell_state: &mut ShellState, script: &str, origin: &str) -> ! {
+    process_script(shell_state, script, Some(origin));
+    exit_shell(shell_state);
+}
+
+/// Reads the script at `path`, or from stdin without a path, exiting if it can't be read.
+fn read_script(path: Option<&str>) -> String {
+    let result = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut script = String::new();
+            std::io::stdin().read_to_string(&mut script).map(|_| script)
+        }
+    };
+    match result {
+        Ok(script) => script,
+        Err(why) => {
+            ShellError::io(path.unwrap_or("stdin"), why).report();
+            std::process::exit(127);
+        }
+    }
+}
+
+/// Checks `script` for syntax errors without running it.
+/// Every error is reported, and the exit status is 2 if there were any.
+fn check_script(script: &str, origin: &str) -> ! {
+    let errors = check(script);
+    for err in &errors {
+        eprintln!("crust: syntax error: {}\n{}", err, err.render(script, Some(origin)));
+    }
+    std::process::exit(if errors.is_empty() { 0 } else { 2 });
+}
+
+/// Prints the syntax tree of `script` as JSON, without running it.
+fn dump_ast(script: &str, origin: &str) -> ! {
+    match parse(script) {
+        Ok(program) => {
+            println!("{}", ast_json::dump(&program));
+            std::process::exit(0);
+        }
+        Err(err) => {
+            eprintln!("crust: syntax error: {}\n{}", err, err.render(script, Some(origin)));
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Runs the startup file at `path`, like `~/.config/crust/crustrc`, if it exists.
+fn run_startup_file(shell_state: &mut ShellState, path: &str) {
+    match std::fs::read_to_string(path) {
+        Ok(script) => process_script(shell_state, &script, Some(path)),
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => (),
+        Err(why) => {
+            ShellError::io(path, why).report();
+        }
+    }
+}
+
+/// Turns on the options given on the command line, like `-eux` or `-o pipefail`, with the `set` builtin.
+fn apply_options(shell_state: &mut ShellState, options: &[String]) {
+    if options.is_empty() {
+        return;
+    }
+    match builtins::set::set(shell_state, options) {
+        Ok(output) => print!("{}", output),
+        Err(why) => {
+            why.report();
+            std::process::exit(2);
+        }
+    }
+}
 
-/// A function to parse input, used for the barebones prompt.
-pub fn parse_input(op: &str) -> String {
-    if op == "interactive" {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).expect("failed to read user input");
-        input.trim().to_string()
-    } else {
-        std::env::args()
-            .collect::<Vec<String>>()
-            .get(2)
-            .unwrap()
-            .replace('"', "")
-            .trim()
-            .to_string()
+/// Runs the commands given with `-c`, in a script, or on stdin if it isn't a terminal, and then exits.
+/// With `-n` the commands are only checked for syntax errors, and with `--dump-ast`
+/// what they were parsed into is printed instead.
+pub fn non_interactive(shell_state: &mut ShellState, cli: &Cli) -> ! {
+    let (script, origin) = match &cli.source {
+        Source::Command(input) => (input.clone(), "-c"),
+        Source::Script(path) => (read_script(Some(path)), path.as_str()),
+        Source::Stdin => (read_script(None), "<stdin>"),
+    };
+    match cli.action {
+        Action::Check => check_script(&script, origin),
+        Action::DumpAst => dump_ast(&script, origin),
+        _ => run_script(shell_state, &script, origin),
     }
 }
 
-/// A helper function to run a non-interactive command,
-/// it will automatically check if `-c` was passed as an arg
-/// and run commands non-interactively.
-pub fn non_interactive(shell_state: &mut ShellState) {
-    if shell_state.args.get(1).unwrap_or(&shell_state.na) == "-c" {
-        let input = parse_input("non-interactive");
-        process_input(shell_state, &input);
-        std::process::exit(0);
+/// Sets `setting` to the value of a config option, keeping the default if the value is invalid.
+#[cfg(feature = "readline")]
+fn parse_option<T: std::str::FromStr>(setting: &mut T, (name, value): &(String, String)) {
+    match value.parse() {
+        Ok(value) => *setting = value,
+        Err(_) => println!("[WARNING]: '{}' is an invalid value for '{}', ignoring.", value, name),
     }
 }
 
 fn main() {
     let mut shell_state = ShellState::init();
+    let cli = match cli::parse(&shell_state.arg0, &shell_state.args[1..]) {
+        Ok(cli) => cli,
+        Err(why) => {
+            why.report();
+            eprintln!("Try 'crust --help' for more information.");
+            std::process::exit(2);
+        }
+    };
+    match cli.action {
+        Action::Help => {
+            print!("{}", cli::USAGE);
+            std::process::exit(0);
+        }
+        Action::Version => {
+            println!("crust {}", env!("CARGO_PKG_VERSION"));
+            std::process::exit(0);
+        }
+        _ => (),
+    }
+    if let Some(config) = &cli.config {
+        shell_state.config = config.clone();
+    }
     // Default config:
     // ```
     // bell style="nothing"
@@ -65,10 +180,10 @@ fn main() {
             match option.0.as_str() {
                 "bell style" => shell_state.bell_style = option.1,
                 "edit mode" => shell_state.edit_mode = option.1,
-                "history auto add lines" => shell_state.history_auto_add_lines = option.1.parse::<bool>().unwrap(),
+                "history auto add lines" => parse_option(&mut shell_state.history_auto_add_lines, &option),
                 "history file" => shell_state.history_file = option.1,
-                "history size" => shell_state.history_size = option.1.parse::<usize>().unwrap(),
-                "history spaces ignored" => shell_state.history_spaces_ignored = option.1.parse::<bool>().unwrap(),
+                "history size" => parse_option(&mut shell_state.history_size, &option),
+                "history spaces ignored" => parse_option(&mut shell_state.history_spaces_ignored, &option),
                 "prompt" => shell_state.prompt = option.1,
                 _ => println!("[WARNING]: '{}' is an invalid option, ignoring.", option.0)
             }
@@ -95,10 +210,34 @@ fn main() {
         .history_ignore_space(shell_state.history_spaces_ignored)
         .max_history_size(shell_state.history_size)
         .build();
-    non_interactive(&mut shell_state);
+    signals::init();
+    apply_options(&mut shell_state, &cli.options);
+    if let Some(arg0) = &cli.arg0 {
+        shell_state.arg0 = arg0.clone();
+    }
+    shell_state.positional = cli.positional.clone();
+    if cli.login && !cli.noprofile {
+        let profile = [&shell_state.config_dir, "profile"].concat();
+        run_startup_file(&mut shell_state, &profile);
+    }
+    let interactive =
+        cli.action == Action::Run && (cli.interactive || (cli.source == Source::Stdin && std::io::stdin().is_terminal()));
+    if interactive && !cli.norc {
+        let rc = [&shell_state.config_dir, "crustrc"].concat();
+        run_startup_file(&mut shell_state, &rc);
+    }
+    // `-i` with `-c` or a script still runs those, just with job control and the signals of an interactive shell.
+    if !interactive || cli.source != Source::Stdin {
+        if interactive {
+            init_interactive(&mut shell_state);
+        }
+        non_interactive(&mut shell_state, &cli);
+    }
     #[cfg(feature = "readline")]
     let mut rl = Editor::with_config(config);
     #[cfg(feature = "readline")]
+    rl.set_helper(Some(completion::ShellHelper::default()));
+    #[cfg(feature = "readline")]
     if rl.load_history(&shell_state.history_file).is_err() {
         println!("There was no previous history to load.");
     }
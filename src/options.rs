@@ -0,0 +1,95 @@
+/// A single shell option, which is one bit in `Options`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShellOption {
+    /// The shell exits when a command fails, unless its exit status is checked, e.g. by `if` or `&&`.
+    Errexit,
+    /// Patterns that don't match any files are an error, and the command isn't run.
+    Failglob,
+    /// `>` doesn't overwrite existing files, only `>|` does.
+    Noclobber,
+    /// Patterns like `*.rs` aren't replaced by the paths they match.
+    Noglob,
+    /// Expanding a variable that is not set is an error, unless it has a default, like `${var:-}`.
+    Nounset,
+    /// Patterns that don't match any files expand to nothing, instead of being left as-is.
+    Nullglob,
+    /// The exit status of a pipeline is the one of the last command in it that failed,
+    /// instead of the one of the last command.
+    Pipefail,
+    /// Every command is printed to stderr after it was expanded, behind the value of `$PS4`.
+    Xtrace,
+}
+
+impl ShellOption {
+    /// Every option, sorted by name.
+    pub const ALL: [ShellOption; 8] = [
+        ShellOption::Errexit,
+        ShellOption::Failglob,
+        ShellOption::Noclobber,
+        ShellOption::Noglob,
+        ShellOption::Nounset,
+        ShellOption::Nullglob,
+        ShellOption::Pipefail,
+        ShellOption::Xtrace,
+    ];
+
+    /// The name used with `set -o`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ShellOption::Errexit => "errexit",
+            ShellOption::Failglob => "failglob",
+            ShellOption::Noclobber => "noclobber",
+            ShellOption::Noglob => "noglob",
+            ShellOption::Nounset => "nounset",
+            ShellOption::Nullglob => "nullglob",
+            ShellOption::Pipefail => "pipefail",
+            ShellOption::Xtrace => "xtrace",
+        }
+    }
+
+    /// Returns the option called `name`, or `None` if there is no such option.
+    pub fn from_name(name: &str) -> Option<ShellOption> {
+        ShellOption::ALL.iter().copied().find(|option| option.name() == name)
+    }
+
+    /// Returns the option that is set with a flag, like `errexit` for the `e` in `set -e`.
+    pub fn from_flag(flag: char) -> Option<ShellOption> {
+        match flag {
+            'C' => Some(ShellOption::Noclobber),
+            'e' => Some(ShellOption::Errexit),
+            'f' => Some(ShellOption::Noglob),
+            'u' => Some(ShellOption::Nounset),
+            'x' => Some(ShellOption::Xtrace),
+            _ => None,
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Options that change how the shell behaves, as a bitset with one bit for every `ShellOption`.
+/// They are turned on with `set -o NAME` and off with `set +o NAME`,
+/// or with the flag of the option, like `set -e` and `set +e`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Options(u16);
+
+impl Options {
+    pub fn is_set(self, option: ShellOption) -> bool {
+        self.0 & option.bit() != 0
+    }
+
+    pub fn set(&mut self, option: ShellOption, enabled: bool) {
+        if enabled {
+            self.0 |= option.bit();
+        } else {
+            self.0 &= !option.bit();
+        }
+    }
+
+    /// Returns every option with whether it is on, sorted by name.
+    pub fn list(self) -> Vec<(&'static str, bool)> {
+        ShellOption::ALL.iter().map(|option| (option.name(), self.is_set(*option))).collect()
+    }
+}
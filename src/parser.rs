@@ -0,0 +1,855 @@
+use crate::lexer::{lex, Operator, RedirectOp, Span, Token, TokenKind, Word, WordPart};
+use crate::variables::is_valid_name;
+use std::fmt::{Display, Formatter};
+
+/// A redirection of one of a command's file descriptors, e.g. `2> file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// The file descriptor in front of the operator, if there was one.
+    pub fd: Option<u32>,
+    pub op: RedirectOp,
+    /// A file, or a file descriptor for `>&` and `<&`.
+    pub target: Word,
+}
+
+/// A variable assignment in front of a command, e.g. `FOO=bar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+/// A single command: the assignments in front of it, a name,
+/// its arguments, and its redirections.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SimpleCommand {
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// An arm of a `case`, e.g. `*.rs | *.toml) echo rust ;;`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseArm {
+    pub patterns: Vec<Word>,
+    pub body: Program,
+}
+
+/// A command that is made of other commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompoundCommand {
+    /// `if condition; then body; elif condition; then body; else body; fi`
+    If {
+        /// The condition and body of the `if` and every `elif`.
+        branches: Vec<(Program, Program)>,
+        else_branch: Option<Program>,
+    },
+    /// `while condition; do body; done`, or `until condition; do body; done`
+    /// if `until` is `true`, which runs as long as the condition fails.
+    While {
+        condition: Program,
+        body: Program,
+        until: bool,
+    },
+    /// `for name in words; do body; done`. Without `in words`,
+    /// it loops over the positional parameters.
+    For {
+        name: String,
+        words: Option<Vec<Word>>,
+        body: Program,
+    },
+    /// `case word in pattern) body ;; esac`
+    Case { word: Word, arms: Vec<CaseArm> },
+    /// `{ body; }`, which runs the body in the current shell.
+    BraceGroup(Program),
+}
+
+/// A function definition, e.g. `greet() { echo hello $1; }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub body: CompoundCommand,
+    /// The redirections after the body, which apply every time the function is called.
+    pub redirects: Vec<Redirect>,
+}
+
+/// A single command in a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// A compound command, with the redirections after it, e.g. `while ...; done < file`.
+    Compound(CompoundCommand, Vec<Redirect>),
+    Function(FunctionDefinition),
+}
+
+/// One or more commands connected by `|`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+    /// Whether the pipeline starts with `!`, which inverts its exit status.
+    pub negated: bool,
+    /// The pipeline as it was written, e.g. for `jobs`.
+    pub text: String,
+}
+
+/// How two pipelines in an `AndOrList` are connected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`, run the next pipeline if the previous one succeeded.
+    And,
+    /// `||`, run the next pipeline if the previous one failed.
+    Or,
+}
+
+/// Pipelines connected by `&&` and `||`, e.g. `make && ./run || echo failed`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    /// Whether the list ends with `&`, so it runs in the background.
+    pub background: bool,
+}
+
+/// Everything that was parsed from the input, in order.
+/// Lists are separated by `;` or newlines.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Program {
+    pub lists: Vec<AndOrList>,
+}
+
+/// The error returned when input could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    /// Whether the input just ended too early, e.g. in the middle of an `if`.
+    /// The interactive prompt then asks for more lines instead of showing the error.
+    pub incomplete: bool,
+    /// Shown next to the caret under the error, e.g. where an unterminated quote started.
+    pub hint: Option<String>,
+}
+
+impl ParseError {
+    /// Shows where the error is in `source` like rustc does, with the line it is on,
+    /// a caret under it and the hint next to the caret:
+    ///
+    /// ```text
+    ///  --> script.crust:2:6
+    ///   |
+    /// 2 | echo "hello
+    ///   |      ^ unterminated double quote started here
+    /// ```
+    ///
+    /// `origin` is where `source` came from, like the path of a script.
+    /// Without it, only the line and column are shown.
+    pub fn render(&self, source: &str, origin: Option<&str>) -> String {
+        // An error at the very end points just past the last character of the last line.
+        let start = self.span.start.min(source.trim_end_matches('\n').len());
+        let line_start = source[..start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |idx| start + idx);
+        let line_number = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let width = source[start..self.span.end.clamp(start, line_end)].chars().count().max(1);
+        // Tabs are kept, so the caret lines up with the line above it.
+        let padding = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let location = match origin {
+            Some(origin) => format!("{}:{}:{}", origin, line_number, column),
+            None => format!("line {}, column {}", line_number, column),
+        };
+        let gutter = " ".repeat(line_number.to_string().len());
+        let mut output = format!(
+            "{0}--> {1}\n{0} |\n{2} | {3}\n{0} | {4}{5}",
+            gutter,
+            location,
+            line_number,
+            &source[line_start..line_end],
+            padding,
+            "^".repeat(width)
+        );
+        if let Some(hint) = &self.hint {
+            output.push(' ');
+            output.push_str(hint);
+        }
+        output
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Returns the assignment if `word` looks like `NAME=value`.
+fn as_assignment(word: &Word) -> Option<Assignment> {
+    let literal = match word.parts.first() {
+        Some(WordPart::Literal(literal)) => literal,
+        _ => return None,
+    };
+    let (name, value) = literal.split_at(literal.find('=')?);
+    if !is_valid_name(name) {
+        return None;
+    }
+    let mut value_parts = Vec::new();
+    if value.len() > 1 {
+        value_parts.push(WordPart::Literal(value[1..].to_string()));
+    }
+    value_parts.extend(word.parts[1..].iter().cloned());
+    Some(Assignment {
+        name: name.to_string(),
+        value: Word { parts: value_parts },
+    })
+}
+
+/// Words that start or end a compound command, when they are the first word of a command.
+const RESERVED_WORDS: &[&str] = &[
+    "case", "do", "done", "elif", "else", "esac", "fi", "for", "if", "then", "until", "while", "{", "}",
+];
+
+/// Returns the text of `word` if it is a plain, unquoted word.
+fn literal(word: &Word) -> Option<&str> {
+    match word.parts.as_slice() {
+        [WordPart::Literal(literal)] => Some(literal),
+        _ => None,
+    }
+}
+
+/// A recursive descent parser working on the tokens from `lexer::lex`.
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Where the input ends, used for errors at the end of input.
+    end: usize,
+    /// The errors that were skipped over, if all errors in the input are collected.
+    /// Otherwise, parsing stops at the first one.
+    errors: Option<Vec<ParseError>>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// The span of the next token, or an empty span at the end of the input.
+    fn next_span(&self) -> Span {
+        self.peek().map_or(Span::new(self.end, self.end), |token| token.span)
+    }
+
+    /// The error for the next token.
+    fn error_at_next(&self, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            span: self.next_span(),
+            incomplete: self.peek().is_none(),
+            hint: None,
+        }
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError> {
+        Err(self.error_at_next(message))
+    }
+
+    /// Records `err` and skips the rest of the line it is on, if all errors are collected,
+    /// so parsing can go on with the next line. Otherwise, or at the end of the input, `err` is returned.
+    fn recover(&mut self, err: ParseError) -> Result<(), ParseError> {
+        match &mut self.errors {
+            Some(errors) if !err.incomplete => errors.push(err),
+            _ => return Err(err),
+        }
+        while let Some(token) = self.next() {
+            if token.kind == TokenKind::Newline {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the next token if it is a reserved word, without consuming it.
+    fn peek_keyword(&self) -> Option<&'static str> {
+        match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Word(word)) => {
+                let text = literal(word)?;
+                RESERVED_WORDS.iter().copied().find(|keyword| *keyword == text)
+            }
+            _ => None,
+        }
+    }
+
+    fn peek_operator(&self) -> Option<Operator> {
+        match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Operator(op)) => Some(*op),
+            _ => None,
+        }
+    }
+
+    /// Consumes the reserved word `keyword`, or fails if the next token is something else.
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.peek_keyword() != Some(keyword) {
+            return self.error(&format!("expected `{}`", keyword));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Consumes the next token if it is a word, returning it.
+    /// A `!` is a word as well, except in front of a pipeline.
+    fn next_word(&mut self) -> Option<Word> {
+        let word = match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Word(word)) => word.clone(),
+            Some(TokenKind::Bang) => Word {
+                parts: vec![WordPart::Literal(String::from("!"))],
+            },
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(word)
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Some(Token {
+            kind: TokenKind::Newline,
+            ..
+        }) = self.peek()
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn parse_program(&mut self) -> Result<Program, ParseError> {
+        let mut program = self.parse_list()?;
+        // Whatever ended the list doesn't belong to anything, like a `fi` without an `if`.
+        while let Some(token) = self.peek() {
+            let message = match &token.kind {
+                TokenKind::Word(word) => format!("unexpected `{}`", literal(word).unwrap_or_default()),
+                _ => String::from("unexpected token"),
+            };
+            self.recover(self.error_at_next(&message))?;
+            program.lists.extend(self.parse_list()?.lists);
+        }
+        Ok(program)
+    }
+
+    /// Parses lists separated by `;` or newlines, until the end of the input, a reserved word
+    /// like `fi` or `done`, or a `;;` or `)`, which the caller then handles.
+    fn parse_list(&mut self) -> Result<Program, ParseError> {
+        let mut program = Program::default();
+        self.skip_newlines();
+        while self.peek().is_some() && !self.at_list_end() {
+            match self.parse_and_or() {
+                Ok(list) => program.lists.push(list),
+                Err(err) => {
+                    self.recover(err)?;
+                    continue;
+                }
+            }
+            match self.peek_operator() {
+                Some(Operator::Background) => {
+                    program.lists.last_mut().unwrap().background = true;
+                    self.pos += 1;
+                    self.skip_newlines();
+                }
+                Some(Operator::Semicolon) => {
+                    self.pos += 1;
+                    self.skip_newlines();
+                }
+                _ if matches!(self.peek().map(|token| &token.kind), Some(TokenKind::Newline)) => self.skip_newlines(),
+                _ if self.peek().is_none() || self.at_list_end() => (),
+                _ => self.recover(self.error_at_next("expected `;` or a newline"))?,
+            }
+        }
+        Ok(program)
+    }
+
+    /// Checks if the next token ends a list.
+    fn at_list_end(&self) -> bool {
+        matches!(self.peek_keyword(), Some("do" | "done" | "elif" | "else" | "esac" | "fi" | "then" | "}"))
+            || matches!(self.peek_operator(), Some(Operator::DoubleSemicolon | Operator::RightParen))
+    }
+
+    /// Parses a list that has to have at least one command in it, like the body of a loop.
+    fn parse_body(&mut self, keyword: &str) -> Result<Program, ParseError> {
+        let program = self.parse_list()?;
+        if program.lists.is_empty() {
+            return self.error(&format!("expected a command after `{}`", keyword));
+        }
+        Ok(program)
+    }
+
+    fn parse_and_or(&mut self) -> Result<AndOrList, ParseError> {
+        let mut list = AndOrList {
+            first: self.parse_pipeline()?,
+            rest: Vec::new(),
+            background: false,
+        };
+        loop {
+            let connector = match self.peek().map(|token| &token.kind) {
+                Some(TokenKind::Operator(Operator::And)) => Connector::And,
+                Some(TokenKind::Operator(Operator::Or)) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            // The next pipeline is allowed to be on the next line.
+            self.skip_newlines();
+            list.rest.push((connector, self.parse_pipeline()?));
+        }
+        Ok(list)
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let start = self.next_span().start;
+        let negated = matches!(self.peek().map(|token| &token.kind), Some(TokenKind::Bang));
+        if negated {
+            self.pos += 1;
+        }
+        let mut pipeline = Pipeline {
+            commands: vec![self.parse_command()?],
+            negated,
+            text: String::new(),
+        };
+        while let Some(TokenKind::Operator(Operator::Pipe)) = self.peek().map(|token| &token.kind) {
+            self.pos += 1;
+            // The next command is allowed to be on the next line.
+            self.skip_newlines();
+            pipeline.commands.push(self.parse_command()?);
+        }
+        let end = self.tokens[self.pos - 1].span.end;
+        pipeline.text = self.input[start..end].to_string();
+        Ok(pipeline)
+    }
+
+    /// Checks if the next tokens are the start of a function definition, like `name()`.
+    fn at_function_definition(&self) -> bool {
+        let name = match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Word(word)) => literal(word),
+            _ => None,
+        };
+        name.is_some_and(|name| is_valid_name(name) && !RESERVED_WORDS.contains(&name))
+            && matches!(
+                self.tokens.get(self.pos + 1).map(|token| &token.kind),
+                Some(TokenKind::Operator(Operator::LeftParen))
+            )
+    }
+
+    fn parse_function_definition(&mut self) -> Result<Command, ParseError> {
+        let name = self.next_word().as_ref().and_then(literal).unwrap_or_default().to_string();
+        self.pos += 1;
+        if self.peek_operator() != Some(Operator::RightParen) {
+            return self.error("expected `)`");
+        }
+        self.pos += 1;
+        self.skip_newlines();
+        if self.peek_keyword().is_none() {
+            return self.error(&format!("expected a compound command like `{{ ...; }}` as the body of `{}`", name));
+        }
+        match self.parse_command()? {
+            Command::Compound(body, redirects) => Ok(Command::Function(FunctionDefinition { name, body, redirects })),
+            _ => self.error(&format!("expected a compound command as the body of `{}`", name)),
+        }
+    }
+
+    fn parse_command(&mut self) -> Result<Command, ParseError> {
+        if self.at_function_definition() {
+            return self.parse_function_definition();
+        }
+        let compound = match self.peek_keyword() {
+            Some("if") => self.parse_if()?,
+            Some(keyword @ ("while" | "until")) => {
+                let until = keyword == "until";
+                self.pos += 1;
+                let condition = self.parse_body(keyword)?;
+                self.expect_keyword("do")?;
+                let body = self.parse_body("do")?;
+                self.expect_keyword("done")?;
+                CompoundCommand::While { condition, body, until }
+            }
+            Some("for") => self.parse_for()?,
+            Some("case") => self.parse_case()?,
+            Some("{") => {
+                self.pos += 1;
+                let body = self.parse_body("{")?;
+                self.expect_keyword("}")?;
+                CompoundCommand::BraceGroup(body)
+            }
+            Some(keyword) => return self.error(&format!("unexpected `{}`", keyword)),
+            None => return self.parse_simple_command().map(Command::Simple),
+        };
+        let mut redirects = Vec::new();
+        while let Some(TokenKind::Redirect(..)) = self.peek().map(|token| &token.kind) {
+            redirects.push(self.parse_redirect()?);
+        }
+        Ok(Command::Compound(compound, redirects))
+    }
+
+    fn parse_if(&mut self) -> Result<CompoundCommand, ParseError> {
+        let mut branches = Vec::new();
+        let mut else_branch = None;
+        let mut keyword = "if";
+        self.pos += 1;
+        loop {
+            let condition = self.parse_body(keyword)?;
+            self.expect_keyword("then")?;
+            branches.push((condition, self.parse_body("then")?));
+            match self.peek_keyword() {
+                Some("elif") => {
+                    keyword = "elif";
+                    self.pos += 1;
+                }
+                Some("else") => {
+                    self.pos += 1;
+                    else_branch = Some(self.parse_body("else")?);
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+            }
+        }
+        Ok(CompoundCommand::If { branches, else_branch })
+    }
+
+    fn parse_for(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let name = match self.next_word().as_ref().map(literal) {
+            Some(Some(name)) if is_valid_name(name) => name.to_string(),
+            Some(_) => {
+                self.pos -= 1;
+                return self.error("expected a variable name after `for`");
+            }
+            None => return self.error("expected a variable name after `for`"),
+        };
+        self.skip_newlines();
+        let mut words = None;
+        if self.peek().map(|token| &token.kind).and_then(|kind| match kind {
+            TokenKind::Word(word) => literal(word),
+            _ => None,
+        }) == Some("in")
+        {
+            self.pos += 1;
+            let mut in_words = Vec::new();
+            while let Some(word) = self.next_word() {
+                in_words.push(word);
+            }
+            words = Some(in_words);
+        }
+        match self.peek_operator() {
+            Some(Operator::Semicolon) => self.pos += 1,
+            _ if words.is_some() && !matches!(self.peek().map(|token| &token.kind), Some(TokenKind::Newline)) => {
+                return self.error("expected `;` or a newline");
+            }
+            _ => (),
+        }
+        self.skip_newlines();
+        self.expect_keyword("do")?;
+        let body = self.parse_body("do")?;
+        self.expect_keyword("done")?;
+        Ok(CompoundCommand::For { name, words, body })
+    }
+
+    fn parse_case(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let word = match self.next_word() {
+            Some(word) => word,
+            None => return self.error("expected a word after `case`"),
+        };
+        self.skip_newlines();
+        match self.next_word() {
+            Some(word) if literal(&word) == Some("in") => (),
+            _ => {
+                self.pos -= 1;
+                return self.error("expected `in`");
+            }
+        }
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.peek_keyword() == Some("esac") {
+                self.pos += 1;
+                break;
+            }
+            // The `(` in front of the patterns is optional.
+            if self.peek_operator() == Some(Operator::LeftParen) {
+                self.pos += 1;
+            }
+            let mut patterns = Vec::new();
+            loop {
+                match self.next_word() {
+                    Some(pattern) => patterns.push(pattern),
+                    None => return self.error("expected a pattern"),
+                }
+                match self.peek_operator() {
+                    Some(Operator::Pipe) => self.pos += 1,
+                    Some(Operator::RightParen) => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return self.error("expected `)` or `|` after a pattern"),
+                }
+            }
+            let body = self.parse_list()?;
+            arms.push(CaseArm { patterns, body });
+            match self.peek_operator() {
+                Some(Operator::DoubleSemicolon) => self.pos += 1,
+                _ => {
+                    self.skip_newlines();
+                    self.expect_keyword("esac")?;
+                    break;
+                }
+            }
+        }
+        Ok(CompoundCommand::Case { word, arms })
+    }
+
+    fn parse_redirect(&mut self) -> Result<Redirect, ParseError> {
+        let (fd, op) = match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Redirect(fd, op)) => (*fd, *op),
+            _ => return self.error("expected a redirection"),
+        };
+        self.pos += 1;
+        match self.next_word() {
+            Some(target) => Ok(Redirect { fd, op, target }),
+            None => self.error("expected a file name after the redirection"),
+        }
+    }
+
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut command = SimpleCommand::default();
+        loop {
+            match self.peek().map(|token| &token.kind) {
+                Some(TokenKind::Word(_) | TokenKind::Bang) => {
+                    if let Some(word) = self.next_word() {
+                        // Assignments are only recognized in front of the command name.
+                        match as_assignment(&word) {
+                            Some(assignment) if command.words.is_empty() => command.assignments.push(assignment),
+                            _ => command.words.push(word),
+                        }
+                    }
+                }
+                Some(TokenKind::Redirect(..)) => command.redirects.push(self.parse_redirect()?),
+                _ => break,
+            }
+        }
+        // Commands with only assignments or redirections, like `> file`, are allowed.
+        if command.words.is_empty() && command.redirects.is_empty() && command.assignments.is_empty() {
+            return self.error("expected a command");
+        }
+        Ok(command)
+    }
+}
+
+/// Parses the input into a `Program`.
+pub fn parse(input: &str) -> Result<Program, ParseError> {
+    let mut parser = Parser {
+        input,
+        tokens: lex(input)?,
+        pos: 0,
+        end: input.len(),
+        errors: None,
+    };
+    parser.parse_program()
+}
+
+/// Parses all of `input` without running it, returning every syntax error in it.
+/// Unlike `parse`, it goes on with the next line after an error. Errors from splitting
+/// the input into tokens, like an unterminated quote, still end the check.
+pub fn check(input: &str) -> Vec<ParseError> {
+    let tokens = match lex(input) {
+        Ok(tokens) => tokens,
+        Err(err) => return vec![err],
+    };
+    let mut parser = Parser {
+        input,
+        tokens,
+        pos: 0,
+        end: input.len(),
+        errors: Some(Vec::new()),
+    };
+    let result = parser.parse_program();
+    let mut errors = parser.errors.unwrap_or_default();
+    errors.extend(result.err());
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of a word without quotes, where expansions are left out.
+    fn text(word: &Word) -> String {
+        word.parts
+            .iter()
+            .map(|part| match part {
+                WordPart::Literal(text) | WordPart::Quoted(text) => text.as_str(),
+                _ => "",
+            })
+            .collect()
+    }
+
+    fn simple(command: &Command) -> &SimpleCommand {
+        match command {
+            Command::Simple(command) => command,
+            _ => panic!("expected a simple command"),
+        }
+    }
+
+    /// The words of a simple command, with the quotes removed.
+    fn words(command: &Command) -> Vec<String> {
+        simple(command).words.iter().map(text).collect()
+    }
+
+    /// Every pipeline in every list, with the connector in front of all but the first,
+    /// and `&` after lists that run in the background.
+    fn structure(input: &str) -> Vec<Vec<String>> {
+        let pipeline_text = |pipeline: &Pipeline| {
+            pipeline.commands.iter().map(|command| words(command).join(" ")).collect::<Vec<_>>().join(" | ")
+        };
+        parse(input)
+            .unwrap()
+            .lists
+            .iter()
+            .map(|list| {
+                let mut pipelines = vec![pipeline_text(&list.first)];
+                for (connector, pipeline) in &list.rest {
+                    let connector = match connector {
+                        Connector::And => "&&",
+                        Connector::Or => "||",
+                    };
+                    pipelines.push(format!("{} {}", connector, pipeline_text(pipeline)));
+                }
+                if list.background {
+                    pipelines.push(String::from("&"));
+                }
+                pipelines
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pipelines() {
+        let program = parse("echo a > out | wc -l >> log\nls").unwrap();
+        assert_eq!(program.lists.len(), 2);
+        let commands = &program.lists[0].first.commands;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(words(&commands[0]), vec!["echo", "a"]);
+        assert_eq!(simple(&commands[0]).redirects[0].op, RedirectOp::Overwrite);
+        assert_eq!(text(&simple(&commands[0]).redirects[0].target), "out");
+        assert_eq!(words(&commands[1]), vec!["wc", "-l"]);
+        assert_eq!(simple(&commands[1]).redirects[0].op, RedirectOp::Append);
+        assert_eq!(words(&program.lists[1].first.commands[0]), vec!["ls"]);
+    }
+
+    #[test]
+    fn redirections() {
+        let program = parse("cmd 2>&1 < in 3> out").unwrap();
+        let redirects = &simple(&program.lists[0].first.commands[0]).redirects;
+        assert_eq!(redirects.len(), 3);
+        assert_eq!((redirects[0].fd, redirects[0].op), (Some(2), RedirectOp::DupOutput));
+        assert_eq!(text(&redirects[0].target), "1");
+        assert_eq!((redirects[1].fd, redirects[1].op), (None, RedirectOp::Input));
+        assert_eq!((redirects[2].fd, redirects[2].op), (Some(3), RedirectOp::Overwrite));
+        assert_eq!(text(&redirects[2].target), "out");
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(structure("a | b && c || d | e; f"), vec![vec!["a | b", "&& c", "|| d | e"], vec!["f"]]);
+        assert_eq!(structure("a\nb;\n\nc;"), vec![vec!["a"], vec!["b"], vec!["c"]]);
+        assert_eq!(structure("make && ./run || echo failed"), vec![vec!["make", "&& ./run", "|| echo failed"]]);
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(structure("a & b&c"), vec![vec!["a", "&"], vec!["b", "&"], vec!["c"]]);
+        // `&` applies to the whole list, not just the last pipeline.
+        assert_eq!(structure("a && b | c & d"), vec![vec!["a", "&& b | c", "&"], vec!["d"]]);
+        assert!(parse("& a").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("| wc").unwrap_err().message, "expected a command");
+        assert_eq!(parse("ls |").unwrap_err().message, "expected a command");
+        assert_eq!(parse("ls >").unwrap_err().message, "expected a file name after the redirection");
+        assert!(parse("a && && b").is_err());
+        assert!(parse("; a").is_err());
+    }
+
+    fn is_incomplete(input: &str) -> bool {
+        parse(input).unwrap_err().incomplete
+    }
+
+    #[test]
+    fn incomplete() {
+        assert!(is_incomplete("a &&"));
+        assert!(is_incomplete("a |"));
+        assert!(is_incomplete("echo \"abc"));
+        assert!(is_incomplete("if true; then"));
+        assert!(is_incomplete("if true; then echo\nelse"));
+        assert!(is_incomplete("while true; do"));
+        assert!(is_incomplete("for x in a b; do echo $x"));
+        assert!(is_incomplete("case $x in a)"));
+        assert!(is_incomplete("f() {"));
+        assert!(is_incomplete("echo $(ls"));
+        assert!(parse("if true\nthen echo\nfi").is_ok());
+    }
+
+    #[test]
+    fn not_incomplete() {
+        assert!(!is_incomplete("fi"));
+        assert!(!is_incomplete("a && && b"));
+        assert!(!is_incomplete("echo )"));
+        assert!(!is_incomplete("if true; then echo; done"));
+        assert!(!is_incomplete("; a"));
+    }
+
+    #[test]
+    fn render() {
+        let source = "echo a\n\tls |& b";
+        let err = parse(source).unwrap_err();
+        assert_eq!(err.render(source, Some("script")), concat!(
+            " --> script:2:6\n",
+            "  |\n",
+            "2 | \tls |& b\n",
+            "  | \t    ^",
+        ));
+        let err = ParseError {
+            message: String::from("unexpected end of input"),
+            span: Span::new(7, 7),
+            incomplete: true,
+            hint: Some(String::from("here")),
+        };
+        assert_eq!(err.render("if true\n", None), concat!(
+            " --> line 1, column 8\n",
+            "  |\n",
+            "1 | if true\n",
+            "  |        ^ here",
+        ));
+    }
+
+    #[test]
+    fn negated() {
+        let program = parse("! grep -q x file && ! a | b; test ! -f x").unwrap();
+        let list = &program.lists[0];
+        assert!(list.first.negated);
+        assert_eq!(list.first.text, "! grep -q x file");
+        assert_eq!(words(&list.first.commands[0]), vec!["grep", "-q", "x", "file"]);
+        assert!(list.rest[0].1.negated);
+        assert_eq!(list.rest[0].1.commands.len(), 2);
+        // Anywhere else, `!` is an ordinary word.
+        let test = &program.lists[1].first;
+        assert!(!test.negated);
+        assert_eq!(words(&test.commands[0]), vec!["test", "!", "-f", "x"]);
+        assert!(is_incomplete("! a |"));
+    }
+}
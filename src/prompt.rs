@@ -1,5 +1,5 @@
-use crate::commands::ShellCommand;
-use crate::shared_functions::tokenize;
+use crate::lexer::Span;
+use crate::parser::ParseError;
 use std::fmt::{Formatter, Display};
 
 #[derive(Debug, Copy, Clone)]
@@ -151,6 +151,11 @@ impl EsBuilder {
         self.escape_sequence.push(';');
     }
 
+    /// Returns `true` if nothing was appended yet.
+    pub fn is_empty(&self) -> bool {
+        self.escape_sequence == "\x1b["
+    }
+
     pub fn build(&mut self) -> EscapeSequence {
         self.escape_sequence.pop();
         self.escape_sequence.push('m');
@@ -160,281 +165,152 @@ impl EsBuilder {
     }
 }
 
-pub fn get_files_from_input(input: &str) -> Vec<String> {
-    let tokenized_vec = tokenize(input);
-    let mut tmp_vec: String = String::new();
-    let mut file_vec: Vec<String> = Vec::new();
-    let mut file = false;
-    let mut file_end = false;
-    let mut tok_iter = tokenized_vec.iter().peekable();
-    while tok_iter.peek().is_some() {
-        let tok_iter_char = tok_iter.next().unwrap().as_str();
-        if file_end {
-            file_vec.push(tmp_vec.to_string());
-            tmp_vec.clear();
-            file = false;
-            file_end = false;
-            continue;
-        }
-        if tok_iter_char == "%" && tok_iter.peek().unwrap().as_str() == "[" {
-            file = true;
-        } else if file {
-            if tok_iter_char == "[" {
-                continue;
-            } else if tok_iter_char != "]" {
-                tmp_vec.push_str(tok_iter_char);
-            } else if tok_iter_char == "]" {
-                file_end = true;
-                continue;
+/// Returns the text of every sequence that starts with `%` and `open`, up to the next `close`,
+/// like `%(command)` or `%[file]`. A sequence that is never closed is ignored.
+fn get_enclosed(input: &str, open: char, close: char) -> Vec<String> {
+    let start = format!("%{}", open);
+    let mut enclosed = Vec::new();
+    let mut rest = input;
+    while let Some(idx) = rest.find(&start) {
+        rest = &rest[idx + start.len()..];
+        match rest.find(close) {
+            Some(end) => {
+                enclosed.push(rest[..end].to_string());
+                rest = &rest[end + close.len_utf8()..];
             }
-        }
-    } {
-        if file_end {
-            file_vec.push(tmp_vec.to_string());
-            file_vec.clear();
+            None => break,
         }
     }
-    file_vec
+    enclosed
 }
 
-pub fn get_commands_from_input(input: &str) -> Vec<ShellCommand> {
-    let tokenized_vec = tokenize(input);
-    let mut tmp_vec: String = String::new();
-    let mut command_vec: Vec<ShellCommand> = Vec::new();
-    let mut command = false;
-    let mut command_end = false;
-    let mut tok_iter = tokenized_vec.iter().peekable();
-    while tok_iter.peek().is_some() {
-        let tok_iter_char = tok_iter.next().unwrap().as_str();
-        if command_end {
-            command_vec.push(ShellCommand::new(tmp_vec.as_str()));
-            tmp_vec.clear();
-            command = false;
-            command_end = false;
-            continue;
-        }
-        if tok_iter_char == "%" && tok_iter.peek().unwrap().as_str() == "(" {
-            command = true;
-        } else if command {
-            if tok_iter_char == "(" {
-                continue;
-            } else if tok_iter_char != ")" {
-                tmp_vec.push_str(tok_iter_char);
-            } else if tok_iter_char == ")" {
-                command_end = true;
-                continue;
-            }
-        }
-    } {
-        if command_end {
-            command_vec.push(ShellCommand::new(tmp_vec.as_str()));
-            tmp_vec.clear();
-        }
+/// Returns the path of every `%[file]` in the input.
+pub fn get_files_from_input(input: &str) -> Vec<String> {
+    get_enclosed(input, '[', ']')
+}
+
+/// Returns the text of every `%(command)` in the input.
+pub fn get_commands_from_input(input: &str) -> Vec<String> {
+    get_enclosed(input, '(', ')')
+}
+
+/// The `%{...}` sequences that are replaced after the effects, in `ShellState::eval_prompt`.
+const SUBSTITUTIONS: [&str; 7] = ["CL", "CS", "D", "H", "T12", "T24", "U"];
+
+/// Returns the escape code of a color like `GREEN`, as the foreground for `F<...>`
+/// or as the background for `B<...>`.
+fn color_code(kind: char, name: &str) -> Option<u8> {
+    let (fg, bg) = match name {
+        "BLACK" => (FgColor::Black, BgColor::Black),
+        "RED" => (FgColor::Red, BgColor::Red),
+        "GREEN" => (FgColor::Green, BgColor::Green),
+        "YELLOW" => (FgColor::Yellow, BgColor::Yellow),
+        "BLUE" => (FgColor::Blue, BgColor::Blue),
+        "MAGENTA" => (FgColor::Magenta, BgColor::Magenta),
+        "CYAN" => (FgColor::Cyan, BgColor::Cyan),
+        "WHITE" => (FgColor::White, BgColor::White),
+        _ => return None,
+    };
+    let color = if kind == 'F' { Color::Fg(fg) } else { Color::Bg(bg) };
+    match color {
+        Color::Fg(fg) => Some(fg.to_u8()),
+        Color::Bg(bg) => Some(bg.to_u8()),
     }
-    command_vec
 }
 
-pub fn parse_prompt_effects(input: &str) -> String {
-    let tokenized_vec = tokenize(input);
-    let mut tok_iter = tokenized_vec.iter().peekable();
-    let mut es_builder = EscapeSequence::builder();
-    let mut es_seqs: Vec<(String, String)> = Vec::new();
-    let mut fin_prompt = String::new();
-    let mut tmp_string = String::new();
-    let mut pos_option = false;
-    let mut pos_bgcol = false;
-    let mut bgcol = false;
-    let mut fgcol = false;
-    let mut pos_fgcol = false;
-    let mut option = false;
-    let mut option_fin = false;
-    let mut es_fin = false;
-    let mut not_pos_option;
-    // Go through every character in the input, until the end is reached.
-    while tok_iter.peek().is_some() {
-        // Get the next char unwrapping is safe as we ensured that the next char is never None.
-        let cur_char = tok_iter.next().unwrap().as_str();
-        // Match for certain key chars like % for options, F for fgcolor, B for bgcolor.
-        match cur_char {
-            "%" => {
-                not_pos_option = false;
-                if !option_fin {
-                    option_fin = false;
-                }
-                pos_option = true;
-            }
-            "B" => {
-                not_pos_option = false;
-                option_fin = false;
-                pos_bgcol = true;
-                pos_fgcol = false;
-            }
-            "F" => {
-                not_pos_option = false;
-                option_fin = false;
-                pos_bgcol = false;
-                pos_fgcol = true;
-            }
-            _ => {
-                // TODO(zeno): Push every char to the prompt and pop it, if it belongs to an option
-                // if the char wasn't matched, it is not a possbiel option, but could be an
-                // argument for an option, like a color or font effect
-                // this is checked later 
-                not_pos_option = true;
-                // If the option is finished, the escape sequence is finished too, except the char
-                // is matched by a possible identifier.
-                if option_fin {
-                    es_fin = true;
-                }
-            }
-        }
+/// Returns the font effect for the `b` in `%{b}`.
+fn font_effect(name: &str) -> Option<FontEffects> {
+    match name {
+        "b" => Some(FontEffects::Bold),
+        "i" => Some(FontEffects::Italics),
+        "rb" => Some(FontEffects::ResetBackground),
+        "re" => Some(FontEffects::ResetEverything),
+        "rf" => Some(FontEffects::ResetForeground),
+        "u" => Some(FontEffects::Underline),
+        _ => None,
+    }
+}
 
-        // If an escape sequence has finished, build the sequence and push it in the prompt string.
-        if es_fin {
-            //if es_seqs.is_empty() {
-            //    continue;
-            //}
-            // Take the identifier and the sequence out of the vector.
-            // There is an identifier, because we can't know if we meant fg/bg just from the color,
-            // so I (zeno) introduced an indentifier which could be useful,
-            // if one value can mean different things in different contexts.
-            for (ty, seq) in &es_seqs {
-                // Default arg, should be reset to 0.
-                let mut arg = 0;
-                // Actually check for the identifier to know what escape sequence we should use.
-                match ty.as_str() {
-                    "O" => {
-                        arg = match seq.as_str() {
-                            "b" => FontEffects::Bold.to_u8(),
-                            "i" => FontEffects::Italics.to_u8(),
-                            "rb" => FontEffects::ResetBackground.to_u8(),
-                            "re" => FontEffects::ResetEverything.to_u8(),
-                            "rf" => FontEffects::ResetForeground.to_u8(),
-                            "u" => FontEffects::Underline.to_u8(),
-                            _ => 0,
-                        };
-                    }
-                    "B" => {
-                        let tmp_arg = match seq.as_str() {
-                            "BLACK" => Color::Bg(BgColor::Black),
-                            "RED" => Color::Bg(BgColor::Red),
-                            "GREEN" => Color::Bg(BgColor::Green),
-                            "YELLOW" => Color::Bg(BgColor::Yellow),
-                            "BLUE" => Color::Bg(BgColor::Blue),
-                            "MAGENTA" => Color::Bg(BgColor::Magenta),
-                            "CYAN" => Color::Bg(BgColor::Cyan),
-                            _ => Color::Bg(BgColor::White),
-                        };
-                        arg = match tmp_arg {
-                            Color::Fg(fg) => {
-                                fg.to_u8()
-                            },
-                            Color::Bg(bg) => {
-                                bg.to_u8()
-                            }
-                        };
-                    }
-                    "F" => {
-                        let tmp_arg = match seq.as_str() {
-                            "BLACK" => Color::Fg(FgColor::Black),
-                            "RED" => Color::Fg(FgColor::Red),
-                            "GREEN" => Color::Fg(FgColor::Green),
-                            "YELLOW" => Color::Fg(FgColor::Yellow),
-                            "BLUE" => Color::Fg(FgColor::Blue),
-                            "MAGENTA" => Color::Fg(FgColor::Magenta),
-                            "CYAN" => Color::Fg(FgColor::Cyan),
-                            _ => Color::Fg(FgColor::White),
-                        };
-                        arg = match tmp_arg {
-                            Color::Fg(fg) => {
-                                fg.to_u8()
-                            },
-                            Color::Bg(bg) => {
-                                bg.to_u8()
-                            }
-                        };
-                    }
-                    _ => (),
-                }
-                es_builder.append(arg);
-            }
-            // Push finished escape sequence to prompt string.
-            fin_prompt.push_str(es_builder.build().escape_sequence.as_str());
-            // Clear the EsBuilder, so the old escape sequence doenst get expended.
-            es_builder = EsBuilder::new();
-            // Clear the es_seqs vector from any escape sequences, because we begin a new set.
-            es_seqs.clear();
-            option_fin = false;
-            es_fin = false;
-        }
+/// Adds the escape sequence that was built so far to the prompt, if there is one.
+fn push_escape_sequence(prompt: &mut String, es_builder: &mut EsBuilder) {
+    if !es_builder.is_empty() {
+        prompt.push_str(&es_builder.build().to_string());
+        *es_builder = EsBuilder::new();
+    }
+}
 
-        // Check if prev determined possible option, color is actually an option, color
-        // and set appropriate flags for checking
-        if pos_option && cur_char == "{" {
-            option = true;
-            option_fin = false;
-            pos_option = false;
-            continue;
-        } else if pos_bgcol && cur_char == "<" {
-            bgcol = true;
-            option_fin = false;
-            pos_bgcol= false;
-            continue;
-        } else if pos_fgcol && cur_char == "<" {
-            fgcol = true;
-            option_fin = false;
-            pos_fgcol = false;
+/// Replaces the colors and font effects in the prompt, like `F<GREEN>`, `B<BLACK>` or `%{b}`,
+/// with their escape sequences. Effects that follow each other are combined into one sequence.
+/// The `%{...}` sequences in `SUBSTITUTIONS` are kept, so they can be replaced afterwards.
+/// A sequence that isn't closed, or has an unknown color or effect, is kept as it is,
+/// and the first one is returned as the error, so the rest of the prompt still gets its effects.
+pub fn parse_prompt_effects(input: &str) -> (String, Option<ParseError>) {
+    let mut prompt = String::new();
+    let mut error = None;
+    let mut es_builder = EscapeSequence::builder();
+    let mut idx = 0;
+    while let Some(character) = input[idx..].chars().next() {
+        let rest = &input[idx..];
+        let closing = if rest.starts_with("F<") || rest.starts_with("B<") {
+            '>'
+        } else if rest.starts_with("%{") {
+            '}'
+        } else {
+            push_escape_sequence(&mut prompt, &mut es_builder);
+            prompt.push(character);
+            idx += character.len_utf8();
             continue;
-        } 
-
-        // handle found option
-        if option {
-            if cur_char != "}" {
-                // if option doesn't end, push char to tmp_string
-                tmp_string.push_str(cur_char);
-            } else if cur_char == "}" {
-                // if option ends, push option to es_seqs vec
-                option_fin = true;
-                match tmp_string.as_str() {
-                    "b" | "i" | "rb" | "re" | "rf" | "u" => es_seqs.push(("O".to_string(), tmp_string.clone())),
-                    _ => fin_prompt.push_str(format!("%{{{}}}", tmp_string).as_str()),
-                }
-                tmp_string.clear();
-                option = false;
+        };
+        let name_start = idx + 2;
+        let name_end = match input[name_start..].find(closing) {
+            Some(len) => name_start + len,
+            None => {
+                error.get_or_insert(ParseError {
+                    message: format!("unterminated `{}`, missing closing `{}`", &rest[..2], closing),
+                    span: Span::new(idx, name_start),
+                    incomplete: false,
+                    hint: Some(String::from(if closing == '>' { "color started here" } else { "effect started here" })),
+                });
+                push_escape_sequence(&mut prompt, &mut es_builder);
+                prompt.push_str(rest);
+                break;
             }
-            continue;
-        // Handle foudn bgcol
-        } else if bgcol {
-            if cur_char != ">" {
-                // if bgcol doesn't end, push char to tmp_string
-                tmp_string.push_str(cur_char);
-            } else if cur_char == ">" {
-                // if bgcol ends, push option to es_seqs vec
-                option_fin = true;
-                es_seqs.push(("B".to_string(), tmp_string.clone()));
-                tmp_string.clear();
-                bgcol = false;
+        };
+        let name = &input[name_start..name_end];
+        let code = match character {
+            '%' => font_effect(name).map(|effect| effect.to_u8()),
+            _ => color_code(character, name),
+        };
+        match code {
+            Some(code) => es_builder.append(code),
+            None if character == '%' && SUBSTITUTIONS.contains(&name) => {
+                push_escape_sequence(&mut prompt, &mut es_builder);
+                prompt.push_str(&input[idx..=name_end]);
             }
-            continue;
-        // handle found fgcol
-        } else if fgcol {
-            if cur_char != ">" {
-                // if fgcol doesn't end, push char to tmp_string
-                tmp_string.push_str(cur_char);
-            } else if cur_char == ">" {
-                // if fgcol ends, push option to es_seqs vec
-                option_fin = true;
-                es_seqs.push(("F".to_string(), tmp_string.clone()));
-                tmp_string.clear();
-                fgcol = false;
+            None => {
+                let (message, hint) = if character == '%' {
+                    (
+                        format!("unknown effect `%{{{}}}`", name),
+                        "expected one of b, i, rb, re, rf, u, CL, CS, D, H, T12, T24 or U",
+                    )
+                } else {
+                    (
+                        format!("unknown color `{}`", name),
+                        "expected one of BLACK, RED, GREEN, YELLOW, BLUE, MAGENTA, CYAN or WHITE",
+                    )
+                };
+                error.get_or_insert(ParseError {
+                    message,
+                    span: Span::new(name_start, name_end),
+                    incomplete: false,
+                    hint: Some(String::from(hint)),
+                });
+                push_escape_sequence(&mut prompt, &mut es_builder);
+                prompt.push_str(&input[idx..=name_end]);
             }
-            continue;
-        }
-
-        // check if char belongs to an option, otherwise push it as is to prompt string
-        if not_pos_option && (!option || !bgcol || !fgcol) {
-            fin_prompt.push_str(cur_char);
         }
-    };
-    fin_prompt
+        idx = name_end + 1;
+    }
+    push_escape_sequence(&mut prompt, &mut es_builder);
+    (prompt, error)
 }
@@ -1,11 +1,20 @@
-use crate::commands::{cmd_with_output, piped_cmd, return_shellcommand, PipedShellCommand, Redirection, ShellCommand};
+use crate::commands::{cmd_with_output, return_shellcommand, ExitStatus};
+use crate::error::ShellError;
+use crate::executor::{program_output, run_program, ControlFlow};
+use crate::jobs::{init_job_control, Jobs};
+use crate::options::Options;
+use crate::parser::{parse, FunctionDefinition};
+use crate::signals;
+use crate::traps::{run_exit_trap, run_pending, Traps};
+use crate::variables::Variables;
 use sflib::ensure_directory;
+use std::collections::BTreeMap;
 use std::env::var as env_var;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 #[cfg(feature = "readline")]
-use std::process::exit;
-
+use crate::completion::ShellHelper;
 #[cfg(feature = "readline")]
 use rustyline::{error::ReadlineError, Editor};
 
@@ -13,6 +22,7 @@ use rustyline::{error::ReadlineError, Editor};
 use std::io::Write;
 
 /// Holds all important informations for and about the shell.
+#[derive(Clone)]
 pub struct ShellState {
     pub args: Vec<String>,
     pub prompt: String,
@@ -21,6 +31,9 @@ pub struct ShellState {
     pub na: String,
     pub share_dir: String,
     pub cd_prev_dir: Option<PathBuf>,
+    /// The working directory of the shell, which external commands are started in.
+    /// Subshells on their own thread have their own, so they never change the one of the process.
+    pub cwd: PathBuf,
     pub config_dir: String,
     pub config: String,
     pub history_file: String,
@@ -29,6 +42,39 @@ pub struct ShellState {
     pub history_auto_add_lines: bool,
     pub history_size: usize,
     pub history_spaces_ignored: bool,
+    /// The exit status of the last command that was ran.
+    pub exit_status: ExitStatus,
+    /// `$0`, the name of the shell or of the script that is run.
+    pub arg0: String,
+    /// The positional parameters `$1`, `$2`, ..., e.g. the arguments of a script.
+    pub positional: Vec<String>,
+    /// All shell and environment variables.
+    pub variables: Variables,
+    /// The options set with `set -o`.
+    pub options: Options,
+    /// Set by `break` and `continue` until the loop they refer to handles it.
+    pub control_flow: Option<ControlFlow>,
+    /// The number of loops that are currently running.
+    pub loop_depth: usize,
+    /// The functions that were defined, by name.
+    pub functions: BTreeMap<String, Arc<FunctionDefinition>>,
+    /// The number of functions that are currently running.
+    pub function_depth: usize,
+    /// The jobs that were started in the background or stopped.
+    pub jobs: Jobs,
+    /// Whether every pipeline gets its own process group and the terminal while it runs,
+    /// which is only the case for the interactive shell.
+    pub job_control: bool,
+    /// The commands set with `trap`.
+    pub traps: Traps,
+    /// The number of conditions, like the one of an `if`, that are currently running.
+    /// Commands that fail in them don't run the `ERR` trap.
+    pub condition_depth: usize,
+    /// Whether an error in the prompt was shown already.
+    pub prompt_error_reported: bool,
+    /// Whether this is a copy of the shell made by `subshell`. A subshell must not change
+    /// anything that all threads of the process share, like the working directory.
+    pub subshell: bool,
 }
 
 /// Gets the current time with the format specified if the `time` feature is enabled.
@@ -48,36 +94,109 @@ fn get_time(format: &str) -> String {
 
 // Process the input to run the appropriate builtin or external command.
 pub fn process_input(shell_state: &mut ShellState, input: &str) {
-    if input.is_empty() {
-        return;
+    process_script(shell_state, input, None);
+}
+
+/// Like `process_input`, but syntax errors show `origin`, like the path of a script,
+/// in front of their line and column.
+pub fn process_script(shell_state: &mut ShellState, input: &str, origin: Option<&str>) {
+    let program = match parse(input) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("crust: syntax error: {}\n{}", err, err.render(input, origin));
+            shell_state.exit_status = ExitStatus::from_code(2);
+            return;
+        }
+    };
+    run_program(shell_state, &program);
+}
+
+/// Like `process_input`, but returns the output instead of printing it.
+pub fn process_input_with_output(shell_state: &mut ShellState, input: &str) -> String {
+    let program = match parse(input) {
+        Ok(program) => program,
+        Err(err) => return format!("syntax error: {}", err),
+    };
+    program_output(shell_state, &program)
+}
+
+/// Returns the exit code if `exit` was used, so the shell should exit.
+pub fn exit_requested(shell_state: &ShellState) -> Option<i32> {
+    match shell_state.control_flow {
+        Some(ControlFlow::Exit(code)) => Some(code),
+        _ => None,
     }
-    let command = ShellCommand::new(input);
-    ShellCommand::run(shell_state, command);
+}
+
+/// Exits the shell after running the `EXIT` trap, with the exit code given to `exit`,
+/// or with the exit status of the last command.
+pub fn exit_shell(shell_state: &mut ShellState) -> ! {
+    let code = exit_requested(shell_state).unwrap_or(shell_state.exit_status.code);
+    shell_state.control_flow = None;
+    shell_state.exit_status = ExitStatus::from_code(code);
+    run_exit_trap(shell_state);
+    // The trap can use `exit` itself.
+    std::process::exit(exit_requested(shell_state).unwrap_or(code));
+}
+
+/// Checks if `input` is a command that continues on the next line,
+/// e.g. because a quote or an `if` was not closed yet.
+fn is_incomplete(input: &str) -> bool {
+    matches!(parse(input), Err(err) if err.incomplete)
+}
+
+/// The prompt that is shown while reading the rest of an incomplete command.
+const CONTINUATION_PROMPT: &str = "> ";
+
+/// Returns the prompt for the next line of `input`.
+/// Before a new command is read, the jobs that changed their state are reported.
+fn next_prompt(shell_state: &mut ShellState, input: &str) -> String {
+    signals::clear_interrupt();
+    if !input.is_empty() {
+        return String::from(CONTINUATION_PROMPT);
+    }
+    shell_state.jobs.update();
+    eprint!("{}", shell_state.jobs.report());
+    ShellState::eval_prompt(shell_state)
+}
+
+/// Sets up the signals and job control of an interactive shell.
+pub fn init_interactive(shell_state: &mut ShellState) {
+    signals::init_interactive();
+    shell_state.job_control = init_job_control();
 }
 
 #[cfg(feature = "readline")]
-pub fn run_loop(rl: &mut Editor<()>, mut shell_state: ShellState) {
+pub fn run_loop(rl: &mut Editor<ShellHelper>, mut shell_state: ShellState) {
+    init_interactive(&mut shell_state);
+    let mut input = String::new();
     loop {
-        let prompt = rl.readline(&ShellState::eval_prompt(&mut shell_state));
-        match prompt {
+        run_pending(&mut shell_state);
+        if exit_requested(&shell_state).is_some() {
+            break;
+        }
+        let prompt = next_prompt(&mut shell_state, &input);
+        match rl.readline(&prompt) {
             Ok(line) => {
-                rl.add_history_entry(line.as_str());
-                if line.starts_with("exit") {
-                    if line.contains(' ') {
-                        let input = line.split(' ').collect::<Vec<&str>>()[1];
-                        rl.save_history(&shell_state.history_file).unwrap();
-                        exit(input.parse::<i32>().unwrap_or(0));
-                    } else {
-                        rl.save_history(&shell_state.history_file).unwrap();
-                        exit(0);
-                    }
+                input.push_str(&line);
+                if is_incomplete(&input) {
+                    input.push('\n');
+                    continue;
                 }
-                process_input(&mut shell_state, &line);
+                rl.add_history_entry(input.as_str());
+                process_input(&mut shell_state, &input);
+                input.clear();
             }
             Err(ReadlineError::Interrupted) => {
+                input.clear();
+                shell_state.exit_status = ExitStatus::from_code(130);
                 continue;
             }
             Err(ReadlineError::Eof) => {
+                // Report the command that was never finished.
+                if !input.is_empty() {
+                    process_input(&mut shell_state, &input);
+                }
                 break;
             }
             Err(err) => {
@@ -86,28 +205,77 @@ pub fn run_loop(rl: &mut Editor<()>, mut shell_state: ShellState) {
             }
         }
     }
-    rl.save_history(&shell_state.history_file).unwrap();
+    if let Err(why) = rl.save_history(&shell_state.history_file) {
+        eprintln!("crust: could not save the history to {}: {}", shell_state.history_file, why);
+    }
+    exit_shell(&mut shell_state);
 }
 
 #[cfg(not(feature = "readline"))]
 pub fn run_loop(mut shell_state: ShellState) {
+    init_interactive(&mut shell_state);
+    let mut input = String::new();
     loop {
-        let prompt = ShellState::eval_prompt(&mut shell_state);
+        run_pending(&mut shell_state);
+        if exit_requested(&shell_state).is_some() {
+            break;
+        }
+        let prompt = next_prompt(&mut shell_state, &input);
         print!("{}", prompt);
-        std::io::stdout().flush().unwrap();
-        let input = crate::parse_input("interactive");
+        let _ = std::io::stdout().flush();
+        match signals::read_line() {
+            // Only the line ending is removed, so quoted strings that go on to the next line keep their spaces.
+            Ok(Some(line)) => input.push_str(line.trim_end_matches(['\n', '\r'])),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {
+                // Like with readline, Ctrl-C discards what was typed so far.
+                println!();
+                input.clear();
+                shell_state.exit_status = ExitStatus::from_code(130);
+                continue;
+            }
+            Ok(None) => {
+                // Report the command that was never finished.
+                if !input.is_empty() {
+                    process_input(&mut shell_state, &input);
+                }
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+        if is_incomplete(&input) {
+            input.push('\n');
+            continue;
+        }
         process_input(&mut shell_state, &input);
+        input.clear();
     }
+    exit_shell(&mut shell_state);
 }
 
 impl ShellState {
+    /// Returns a copy of the shell for commands that run on their own thread,
+    /// like builtins in a pipeline, or `$( )`. The copy doesn't do job control or run traps,
+    /// and `break` and `continue` can't leave loops outside of it.
+    pub fn subshell(&self) -> ShellState {
+        let mut shell_state = self.clone();
+        shell_state.loop_depth = 0;
+        shell_state.job_control = false;
+        shell_state.traps = self.traps.for_subshell();
+        shell_state.subshell = true;
+        shell_state
+    }
+
     /// Initalizes the shell state with all the informations needed.
     ///
     /// `cd_prev_dir` doesnt hold a value, because there is no previous dir yet.
     pub fn init() -> ShellState {
-        let args = std::env::args().collect();
+        let args: Vec<String> = std::env::args().collect();
+        let arg0 = args.first().cloned().unwrap_or_else(|| String::from("crust"));
         let prompt = env_var("PROMPT").unwrap_or_else(|_| String::from("F<GREEN>B<BLACK>%{b}%{u}[crust]-[%{CL}]:%{re} "));
-        let user_command = return_shellcommand(String::from("whoami"), Vec::new(), Redirection::NoOp);
+        let user_command = return_shellcommand(String::from("whoami"), Vec::new(), Vec::new());
         let user = env_var("USER").unwrap_or_else(|_| cmd_with_output(&user_command)).trim().to_string();
         let home = env_var("HOME").unwrap_or_else(|_| ["/home/", user.as_str()].concat());
         let na = String::from("no args");
@@ -124,6 +292,8 @@ impl ShellState {
             na,
             share_dir,
             cd_prev_dir,
+            // The working directory can be gone already, e.g. if it was removed by another process.
+            cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             config_dir,
             config,
             history_file,
@@ -132,44 +302,64 @@ impl ShellState {
             history_auto_add_lines: true,
             history_size: 500,
             history_spaces_ignored: true,
+            exit_status: ExitStatus::SUCCESS,
+            arg0,
+            positional: Vec::new(),
+            variables: Variables::from_env(),
+            options: Options::default(),
+            control_flow: None,
+            loop_depth: 0,
+            functions: BTreeMap::new(),
+            function_depth: 0,
+            jobs: Jobs::default(),
+            job_control: false,
+            traps: Traps::default(),
+            condition_depth: 0,
+            prompt_error_reported: false,
+            subshell: false,
         };
-        ensure_directory(&shell_state.share_dir, true).unwrap();
-        ensure_directory(&shell_state.config_dir, true).unwrap();
+        for dir in [&shell_state.share_dir, &shell_state.config_dir] {
+            if let Err(why) = ensure_directory(dir, true) {
+                ShellError::io(format!("could not create {}", dir), why).report();
+            }
+        }
         shell_state
     }
     pub fn eval_prompt(&mut self) -> String {
         let mut evaled_prompt = self.prompt.clone();
         let commands = crate::prompt::get_commands_from_input(&self.prompt);
-        let mut command_output: String;
+        // The commands in the prompt shouldn't change `$?`.
+        let exit_status = self.exit_status;
         for command in commands {
-            if command.args.contains(&String::from("|")) {
-                command_output = piped_cmd(&PipedShellCommand::from(&command));
-            } else {
-                command_output = cmd_with_output(&command);
-            }
-            evaled_prompt = evaled_prompt.replace(
-                format!("%({})", command.to_string().trim()).as_str(),
-                command_output.trim(),
-            );
+            let command_output = process_input_with_output(self, &command);
+            evaled_prompt = evaled_prompt.replace(format!("%({})", command).as_str(), command_output.trim());
         }
+        self.exit_status = exit_status;
         let files = crate::prompt::get_files_from_input(&evaled_prompt);
         for file in files {
             evaled_prompt = evaled_prompt.replace(
                 format!("%[{}]", file.to_string().trim()).as_str(),
-                crate::builtins::cat::cat(&[file]).trim(),
+                crate::builtins::cat::cat(&self.cwd, &[file]).unwrap_or_else(|why| why.to_string()).trim(),
             );
         }
         // Parse the prompt and replace the colors with the escape sequences.
-        evaled_prompt = crate::prompt::parse_prompt_effects(&evaled_prompt);
+        let (prompt, error) = crate::prompt::parse_prompt_effects(&evaled_prompt);
+        if let Some(err) = error {
+            // The prompt is evaluated for every command, but the error is only shown once.
+            if !self.prompt_error_reported {
+                eprintln!("crust: invalid prompt: {}\n{}", err, err.render(&evaled_prompt, Some("prompt")));
+                self.prompt_error_reported = true;
+            }
+        }
+        evaled_prompt = prompt;
+        // The working directory can be gone, e.g. if it was removed by another process.
+        let cwd = std::env::current_dir().map_or_else(|_| String::from("?"), |dir| dir.display().to_string());
         let substitutions = vec!["%{CL}", "%{CS}", "%{D}", "%{H}", "%{T12}", "%{T24}", "%{U}", "\\n"];
         for to_subst in substitutions {
             let mut subst = String::new();
             match to_subst {
-                "%{CL}" => subst = std::env::current_dir().unwrap().display().to_string(),
-                "%{CS}" => {
-                    let cwd = std::env::current_dir().unwrap().display().to_string();
-                    subst = cwd.split('/').collect::<Vec<&str>>()[cwd.split('/').count() - 1].to_string();
-                },
+                "%{CL}" => subst = cwd.clone(),
+                "%{CS}" => subst = cwd.rsplit('/').next().unwrap_or_default().to_string(),
                 "%{D}" => subst = get_time("%a %b %d"),
                 "%{H}" => subst = self.home.clone(),
                 "%{T12}" => subst = get_time("%I:%M %p").to_string(),
@@ -183,79 +373,3 @@ impl ShellState {
         evaled_prompt
     }
 }
-
-/// Tokenizes the input, returning a vector of every character in `input`.
-pub fn tokenize(input: &str) -> Vec<String> {
-    input.chars().map(|t| t.to_string()).collect::<Vec<String>>()
-}
-
-fn push_to_vec(from_vec: &mut Vec<String>, to_vec: &mut Vec<String>) {
-    let element = from_vec.concat();
-    // Don't push to the vector if element is empty.
-    if element.is_empty() {
-        return;
-    }
-    to_vec.push(element);
-    from_vec.clear();
-}
-
-/// Creates a lexified vector from a tokenized one.
-/// Example, if the tokenized vec was:
-/// ```
-/// ["e", "c", "h", "o",
-///  " ",
-///  "\"", "a", "r", "g", " ", "1" "\"",
-///  " ",
-///  "\"", "a", "r", "g", " ", "2" "\""]
-/// ```
-/// It would return:
-/// `["echo", "arg 1", "arg 2"]`
-pub fn lex_tokenized_input(input: &str) -> Vec<String> {
-    let tokenized_vec = tokenize(input);
-    // This is the final vector that will be returned.
-    let mut lexed_vec: Vec<String> = Vec::new();
-    // This is a temporary vec that gets pushed to lexed_vec.
-    let mut tmp_vec: Vec<String> = Vec::new();
-    // Same as tmp_vec except this is for anything in quotes.
-    let mut quoted_vec: Vec<String> = Vec::new();
-    // These two bools are used for checking if the character is in quotes,
-    // and if the quotes part of the match statement was ran.
-    let mut quoted = false;
-    let mut quotes_ran = false;
-    for (idx, character) in tokenized_vec.iter().enumerate() {
-        match character.as_str() {
-            "\"" | "'" => {
-                if quotes_ran {
-                    push_to_vec(&mut quoted_vec, &mut lexed_vec);
-                    quoted = false;
-                    quotes_ran = false;
-                } else {
-                    quoted = true;
-                    quotes_ran = true;
-                }
-            }
-            " " => {
-                if quoted {
-                    quoted_vec.push(character.to_string());
-                } else {
-                    push_to_vec(&mut tmp_vec, &mut lexed_vec);
-                }
-            }
-            // Instead of explicitely checking for everything,
-            // don't we just append any character that doesn't
-            // require extra work, such as quotations.
-            _ => {
-                if quoted {
-                    quoted_vec.push(character.to_string());
-                } else {
-                    tmp_vec.push(character.to_string());
-                    // Needed to push the last element to lexed_vec.
-                    if idx == tokenized_vec.len() - 1 {
-                        push_to_vec(&mut tmp_vec, &mut lexed_vec);
-                    }
-                }
-            }
-        }
-    }
-    lexed_vec
-}
@@ -0,0 +1,317 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Set when the interactive shell gets SIGINT, e.g. from Ctrl-C.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set when a child of the shell exited or was stopped, so its job gets checked.
+static CHILD_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the signal handlers of the interactive shell are installed.
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The signals with a trap that arrived, but whose trap didn't run yet, one bit per signal.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// The signals with a trap, one bit per signal.
+static TRAPPED: AtomicU64 = AtomicU64::new(0);
+
+/// The signals that were ignored with an empty trap, one bit per signal.
+static IGNORED: AtomicU64 = AtomicU64::new(0);
+
+/// The names of the signals that can be used with `trap`, without the `SIG` prefix.
+#[cfg(unix)]
+const NAMES: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("SEGV", libc::SIGSEGV),
+    ("USR2", libc::SIGUSR2),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("SYS", libc::SIGSYS),
+];
+#[cfg(not(unix))]
+const NAMES: &[(&str, i32)] = &[];
+
+/// Returns the number of the signal called `name`, like `INT` or `SIGINT`, or given by its number.
+pub fn number(name: &str) -> Option<i32> {
+    if let Ok(signal) = name.parse() {
+        return NAMES.iter().find(|(_, number)| *number == signal).map(|(_, number)| *number);
+    }
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    NAMES.iter().find(|(signal, _)| *signal == name).map(|(_, number)| *number)
+}
+
+/// Returns the name of `signal` without the `SIG` prefix, like `INT`.
+pub fn name(signal: i32) -> Option<&'static str> {
+    NAMES.iter().find(|(_, number)| *number == signal).map(|(name, _)| *name)
+}
+
+/// Returns the names of all signals, as listed by `trap -l`.
+pub fn names() -> impl Iterator<Item = (&'static str, i32)> {
+    NAMES.iter().copied()
+}
+
+/// The signals the interactive shell catches or ignores,
+/// which get their default handlers back in external commands.
+#[cfg(unix)]
+const SHELL_SIGNALS: [libc::c_int; 6] = [
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+fn bit(signal: i32) -> u64 {
+    1 << signal
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn on_child(_: libc::c_int) {
+    CHILD_CHANGED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn on_trapped_signal(signal: libc::c_int) {
+    if signal == libc::SIGCHLD {
+        CHILD_CHANGED.store(true, Ordering::Relaxed);
+    }
+    PENDING.fetch_or(bit(signal), Ordering::Relaxed);
+}
+
+/// Lets `handler` handle `signal`.
+#[cfg(unix)]
+fn set_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        // Without `SA_RESTART`, reading a line gets interrupted as well.
+        // Children exit at any time though, which shouldn't look like Ctrl-C.
+        action.sa_flags = if signal == libc::SIGCHLD { libc::SA_RESTART } else { 0 };
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut());
+    }
+}
+
+/// Lets the shell notice when its children exit, so the background jobs that are done get reaped.
+/// If SIGCHLD was ignored, children would be reaped automatically
+/// and their exit status would be lost before `wait` or `jobs` sees it.
+pub fn init() {
+    #[cfg(unix)]
+    set_handler(libc::SIGCHLD, on_child);
+}
+
+/// Sets up the signals for the interactive shell, so it doesn't get killed by Ctrl-C or Ctrl-\.
+/// Ctrl-C stops the command that is running in the shell itself, like a loop,
+/// and discards the line that is being typed.
+pub fn init_interactive() {
+    INTERACTIVE.store(true, Ordering::Relaxed);
+    #[cfg(unix)]
+    for signal in [libc::SIGINT, libc::SIGQUIT, libc::SIGTERM] {
+        reset(signal);
+    }
+}
+
+/// Makes `signal` run its trap, once the shell gets to it.
+pub fn catch(signal: i32) {
+    TRAPPED.fetch_or(bit(signal), Ordering::Relaxed);
+    IGNORED.fetch_and(!bit(signal), Ordering::Relaxed);
+    #[cfg(unix)]
+    set_handler(signal, on_trapped_signal);
+}
+
+/// Makes the shell and its commands ignore `signal`.
+pub fn ignore(signal: i32) {
+    TRAPPED.fetch_and(!bit(signal), Ordering::Relaxed);
+    IGNORED.fetch_or(bit(signal), Ordering::Relaxed);
+    #[cfg(unix)]
+    match signal {
+        // The shell still has to know when its children exit.
+        libc::SIGCHLD => set_handler(signal, on_child),
+        _ => unsafe {
+            libc::signal(signal, libc::SIG_IGN);
+        },
+    }
+}
+
+/// Gives `signal` back the handler the shell uses without a trap.
+pub fn reset(signal: i32) {
+    TRAPPED.fetch_and(!bit(signal), Ordering::Relaxed);
+    IGNORED.fetch_and(!bit(signal), Ordering::Relaxed);
+    #[cfg(unix)]
+    {
+        let interactive = INTERACTIVE.load(Ordering::Relaxed);
+        match signal {
+            libc::SIGCHLD => set_handler(signal, on_child),
+            libc::SIGINT if interactive => set_handler(signal, on_interrupt),
+            libc::SIGQUIT | libc::SIGTERM | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU if interactive => unsafe {
+                libc::signal(signal, libc::SIG_IGN);
+            },
+            _ => unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+            },
+        }
+    }
+}
+
+/// Returns the signals that arrived since the last call, for running their traps.
+pub fn take_pending() -> Vec<i32> {
+    let pending = PENDING.swap(0, Ordering::Relaxed);
+    (0..64).filter(|signal| pending & bit(*signal) != 0).collect()
+}
+
+/// Returns `true` if a child exited or was stopped since the last call.
+pub fn take_child_changed() -> bool {
+    CHILD_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+/// Returns `true` if Ctrl-C was pressed since the last call to `clear_interrupt`.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Acts as if Ctrl-C was pressed, e.g. because the command in the foreground was killed by it.
+/// If there is a trap for SIGINT, it runs instead.
+pub fn interrupt() {
+    #[cfg(unix)]
+    if TRAPPED.load(Ordering::Relaxed) & bit(libc::SIGINT) != 0 {
+        PENDING.fetch_or(bit(libc::SIGINT), Ordering::Relaxed);
+        return;
+    }
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+/// Gives an external command the default handlers for the signals the interactive shell
+/// catches or ignores. Signals that were ignored when the shell was started,
+/// or with an empty trap, stay ignored.
+pub fn restore_defaults(command: &mut std::process::Command) {
+    #[cfg(unix)]
+    if INTERACTIVE.load(Ordering::Relaxed) {
+        use std::os::unix::process::CommandExt;
+        let ignored = IGNORED.load(Ordering::Relaxed);
+        // Safety: only async-signal-safe functions are called between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                for signal in SHELL_SIGNALS {
+                    if ignored & bit(signal) == 0 {
+                        libc::signal(signal, libc::SIG_DFL);
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Reads a line from stdin, without the newline at the end.
+/// Returns `Ok(None)` at the end of the input, and an `Interrupted` error if Ctrl-C was pressed.
+#[cfg(not(feature = "readline"))]
+pub fn read_line() -> std::io::Result<Option<String>> {
+    use std::io::BufRead;
+    let stdin = std::io::stdin();
+    let mut stdin = stdin.lock();
+    let mut line = Vec::new();
+    loop {
+        // Unlike `read_line`, `fill_buf` returns when reading was interrupted by a signal.
+        let buffer = stdin.fill_buf()?;
+        if buffer.is_empty() {
+            if line.is_empty() {
+                return Ok(None);
+            }
+            break;
+        }
+        match buffer.iter().position(|&byte| byte == b'\n') {
+            Some(end) => {
+                line.extend_from_slice(&buffer[..end]);
+                stdin.consume(end + 1);
+                break;
+            }
+            None => {
+                let len = buffer.len();
+                line.extend_from_slice(buffer);
+                stdin.consume(len);
+            }
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Returns the message that is shown when a command was killed by `signal`, like `Terminated`.
+#[cfg(unix)]
+pub fn describe(signal: i32) -> String {
+    let message = match signal {
+        libc::SIGHUP => "Hangup",
+        libc::SIGINT => "Interrupt",
+        libc::SIGQUIT => "Quit",
+        libc::SIGILL => "Illegal instruction",
+        libc::SIGTRAP => "Trace/breakpoint trap",
+        libc::SIGABRT => "Aborted",
+        libc::SIGBUS => "Bus error",
+        libc::SIGFPE => "Floating point exception",
+        libc::SIGKILL => "Killed",
+        libc::SIGUSR1 => "User defined signal 1",
+        libc::SIGSEGV => "Segmentation fault",
+        libc::SIGUSR2 => "User defined signal 2",
+        libc::SIGPIPE => "Broken pipe",
+        libc::SIGALRM => "Alarm clock",
+        libc::SIGTERM => "Terminated",
+        libc::SIGXCPU => "CPU time limit exceeded",
+        libc::SIGXFSZ => "File size limit exceeded",
+        libc::SIGSYS => "Bad system call",
+        _ => return format!("Signal {}", signal),
+    };
+    String::from(message)
+}
+
+#[cfg(not(unix))]
+pub fn describe(signal: i32) -> String {
+    format!("Signal {}", signal)
+}
+
+pub fn is_interrupt(signal: i32) -> bool {
+    #[cfg(unix)]
+    return signal == libc::SIGINT;
+    #[cfg(not(unix))]
+    return signal == 2;
+}
+
+pub fn is_broken_pipe(signal: i32) -> bool {
+    #[cfg(unix)]
+    return signal == libc::SIGPIPE;
+    #[cfg(not(unix))]
+    return signal == 13;
+}
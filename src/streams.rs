@@ -0,0 +1,360 @@
+use crate::error::ShellError;
+use crate::lexer::RedirectOp;
+use sflib::ensure_directory;
+use std::fs::{File, OpenOptions};
+use std::io::{PipeReader, PipeWriter, Read, Write};
+#[cfg(unix)]
+use std::os::unix::io::OwnedFd;
+#[cfg(windows)]
+use std::os::windows::io::OwnedHandle as OwnedFd;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A redirection of one of a command's file descriptors,
+/// e.g. `2> file` is `fd: 2, op: Overwrite, target: "file"`.
+#[derive(Debug, Clone)]
+pub struct Redirection {
+    pub fd: u32,
+    pub op: RedirectOp,
+    pub target: String,
+}
+
+/// Where a command reads its input from.
+#[derive(Debug)]
+pub enum Input {
+    /// The shell's own stdin.
+    Stdin,
+    /// The read end of a pipe, e.g. from the previous command in a pipeline.
+    Pipe(PipeReader),
+    File(File),
+    /// A closed file descriptor, e.g. after `<&-`.
+    Closed,
+}
+
+/// Where a command writes its output to.
+#[derive(Debug)]
+pub enum Output {
+    /// The shell's own stdout.
+    Stdout,
+    /// The shell's own stderr.
+    Stderr,
+    /// The write end of a pipe, e.g. to the next command in a pipeline.
+    Pipe(PipeWriter),
+    File(File),
+    /// A closed file descriptor, e.g. after `>&-`.
+    Closed,
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Input::Stdin => std::io::stdin().read(buf),
+            Input::Pipe(pipe) => pipe.read(buf),
+            Input::File(file) => file.read(buf),
+            Input::Closed => Ok(0),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Stdout => std::io::stdout().write(buf),
+            Output::Stderr => std::io::stderr().write(buf),
+            Output::Pipe(pipe) => pipe.write(buf),
+            Output::File(file) => file.write(buf),
+            Output::Closed => Err(std::io::Error::other("bad file descriptor")),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Stdout => std::io::stdout().flush(),
+            Output::Stderr => std::io::stderr().flush(),
+            Output::Pipe(pipe) => pipe.flush(),
+            Output::File(file) => file.flush(),
+            Output::Closed => Ok(()),
+        }
+    }
+}
+
+impl Input {
+    pub fn try_clone(&self) -> std::io::Result<Input> {
+        Ok(match self {
+            Input::Stdin => Input::Stdin,
+            Input::Pipe(pipe) => Input::Pipe(pipe.try_clone()?),
+            Input::File(file) => Input::File(file.try_clone()?),
+            Input::Closed => Input::Closed,
+        })
+    }
+
+    /// Turns the input into a file, which is a duplicate of the shell's stdin for `Stdin`,
+    /// so it can be moved to another file descriptor, like in `3<&0`.
+    fn into_file(self) -> std::io::Result<File> {
+        match self {
+            Input::Stdin => dup_std(0),
+            Input::Pipe(pipe) => Ok(File::from(OwnedFd::from(pipe))),
+            Input::File(file) => Ok(file),
+            Input::Closed => Err(std::io::Error::other("bad file descriptor")),
+        }
+    }
+}
+
+impl Output {
+    pub fn try_clone(&self) -> std::io::Result<Output> {
+        Ok(match self {
+            Output::Stdout => Output::Stdout,
+            Output::Stderr => Output::Stderr,
+            Output::Pipe(pipe) => Output::Pipe(pipe.try_clone()?),
+            Output::File(file) => Output::File(file.try_clone()?),
+            Output::Closed => Output::Closed,
+        })
+    }
+
+    /// Turns the output into a file, which is a duplicate of the shell's own stdout or stderr
+    /// for `Stdout` and `Stderr`, so it can be moved to another file descriptor, like in `3>&1`.
+    fn into_file(self) -> std::io::Result<File> {
+        match self {
+            Output::Stdout => dup_std(1),
+            Output::Stderr => dup_std(2),
+            Output::Pipe(pipe) => Ok(File::from(OwnedFd::from(pipe))),
+            Output::File(file) => Ok(file),
+            Output::Closed => Err(std::io::Error::other("bad file descriptor")),
+        }
+    }
+}
+
+/// Duplicates the shell's own stdin, stdout or stderr.
+fn dup_std(fd: u32) -> std::io::Result<File> {
+    #[cfg(unix)]
+    let owned = {
+        use std::os::unix::io::AsFd;
+        match fd {
+            0 => std::io::stdin().as_fd().try_clone_to_owned(),
+            1 => std::io::stdout().as_fd().try_clone_to_owned(),
+            _ => std::io::stderr().as_fd().try_clone_to_owned(),
+        }
+    };
+    #[cfg(windows)]
+    let owned = {
+        use std::os::windows::io::AsHandle;
+        match fd {
+            0 => std::io::stdin().as_handle().try_clone_to_owned(),
+            1 => std::io::stdout().as_handle().try_clone_to_owned(),
+            _ => std::io::stderr().as_handle().try_clone_to_owned(),
+        }
+    };
+    owned.map(File::from)
+}
+
+/// Duplicates one of the shell's own file descriptors, so it can be given
+/// to a child in a different place, e.g. the shell's stdout as stderr for `2>&1`.
+fn dup_stdio(fd: u32) -> Stdio {
+    dup_std(fd).map_or_else(|_| Stdio::inherit(), Stdio::from)
+}
+
+impl From<Input> for Stdio {
+    fn from(input: Input) -> Stdio {
+        match input {
+            Input::Stdin => Stdio::inherit(),
+            Input::Pipe(pipe) => Stdio::from(pipe),
+            Input::File(file) => Stdio::from(file),
+            Input::Closed => Stdio::null(),
+        }
+    }
+}
+
+impl From<Output> for Stdio {
+    fn from(output: Output) -> Stdio {
+        match output {
+            Output::Stdout => dup_stdio(1),
+            Output::Stderr => dup_stdio(2),
+            Output::Pipe(pipe) => Stdio::from(pipe),
+            Output::File(file) => Stdio::from(file),
+            Output::Closed => Stdio::null(),
+        }
+    }
+}
+
+/// The file descriptors of a command, which redirections are applied to.
+/// `extra` holds file descriptors above 2, which only external commands can use.
+#[derive(Debug)]
+pub struct Streams {
+    pub stdin: Input,
+    pub stdout: Output,
+    pub stderr: Output,
+    pub extra: Vec<(u32, File)>,
+}
+
+impl Default for Streams {
+    fn default() -> Streams {
+        Streams {
+            stdin: Input::Stdin,
+            stdout: Output::Stdout,
+            stderr: Output::Stderr,
+            extra: Vec::new(),
+        }
+    }
+}
+
+/// Opens the target of a redirection, where a relative path is relative to `cwd`.
+/// Missing parent directories are created when writing to a file.
+fn open_file(cwd: &Path, target: &str, op: RedirectOp) -> Result<File, ShellError> {
+    let path = cwd.join(target);
+    let mut options = OpenOptions::new();
+    match op {
+        RedirectOp::Input => options.read(true),
+        RedirectOp::ReadWrite => options.read(true).write(true).create(true),
+        RedirectOp::Append | RedirectOp::AppendOutputAndError => options.append(true).create(true),
+        _ => options.write(true).create(true).truncate(true),
+    };
+    // With `noclobber`, the `Overwrite` that `>` is turned into doesn't replace existing files.
+    if op == RedirectOp::Overwrite && std::fs::metadata(&path).is_ok_and(|metadata| metadata.is_file()) {
+        return Err(ShellError::Redirection(format!("{}: cannot overwrite existing file", target)));
+    }
+    if op != RedirectOp::Input {
+        if let Some(parent_dir) = Path::new(target).parent() {
+            if !parent_dir.as_os_str().is_empty() {
+                let parent_dir = parent_dir.display().to_string();
+                ensure_directory(&cwd.join(&parent_dir).to_string_lossy(), true)
+                    .map_err(|why| ShellError::io(parent_dir, why))?;
+            }
+        }
+    }
+    options.open(path).map_err(|why| ShellError::io(target, why))
+}
+
+impl Streams {
+    /// Duplicates all file descriptors, e.g. so every command in a loop
+    /// can get the streams that were redirected for the whole loop.
+    pub fn try_clone(&self) -> std::io::Result<Streams> {
+        let mut extra = Vec::new();
+        for (fd, file) in &self.extra {
+            extra.push((*fd, file.try_clone()?));
+        }
+        Ok(Streams {
+            stdin: self.stdin.try_clone()?,
+            stdout: self.stdout.try_clone()?,
+            stderr: self.stderr.try_clone()?,
+            extra,
+        })
+    }
+
+    /// Points `fd` at `file`.
+    fn set_file(&mut self, fd: u32, file: File) {
+        match fd {
+            0 => self.stdin = Input::File(file),
+            1 => self.stdout = Output::File(file),
+            2 => self.stderr = Output::File(file),
+            _ => {
+                self.extra.retain(|(extra_fd, _)| *extra_fd != fd);
+                self.extra.push((fd, file));
+            }
+        }
+    }
+
+    /// Makes `fd` a copy of `from`, like `2>&1`.
+    fn duplicate(&mut self, fd: u32, from: u32) -> Result<(), ShellError> {
+        let bad_fd = || ShellError::Redirection(format!("{}: bad file descriptor", from));
+        if fd == from {
+            return Ok(());
+        }
+        let output = match from {
+            0 => Output::File(self.stdin.try_clone().and_then(Input::into_file).map_err(|_| bad_fd())?),
+            1 => self.stdout.try_clone().map_err(|_| bad_fd())?,
+            2 => self.stderr.try_clone().map_err(|_| bad_fd())?,
+            _ => match self.extra.iter().find(|(extra_fd, _)| *extra_fd == from) {
+                Some((_, file)) => Output::File(file.try_clone().map_err(|_| bad_fd())?),
+                None => return Err(bad_fd()),
+            },
+        };
+        match (fd, output) {
+            (1, output) => self.stdout = output,
+            (2, output) => self.stderr = output,
+            // Other file descriptors, including stdin, always need a file of their own,
+            // so the shell's stdout and stderr and pipes are duplicated.
+            (_, output) => self.set_file(fd, output.into_file().map_err(|_| bad_fd())?),
+        }
+        Ok(())
+    }
+
+    /// Closes `fd`, like `>&-`.
+    fn close(&mut self, fd: u32) {
+        match fd {
+            0 => self.stdin = Input::Closed,
+            1 => self.stdout = Output::Closed,
+            2 => self.stderr = Output::Closed,
+            _ => self.extra.retain(|(extra_fd, _)| *extra_fd != fd),
+        }
+    }
+
+    /// Applies a single redirection. Files are opened relative to `cwd`.
+    pub fn redirect(&mut self, redirection: &Redirection, cwd: &Path) -> Result<(), ShellError> {
+        let fd = redirection.fd;
+        let target = redirection.target.as_str();
+        match redirection.op {
+            RedirectOp::DupInput | RedirectOp::DupOutput if target == "-" => self.close(fd),
+            RedirectOp::DupInput | RedirectOp::DupOutput => match target.parse::<u32>() {
+                Ok(from) => self.duplicate(fd, from)?,
+                // `>& file` is the same as `&> file`.
+                Err(_) if redirection.op == RedirectOp::DupOutput && fd == 1 => {
+                    let file = open_file(cwd, target, RedirectOp::OutputAndError)?;
+                    self.stderr = Output::File(file.try_clone().map_err(|why| ShellError::io(target, why))?);
+                    self.stdout = Output::File(file);
+                }
+                Err(_) => return Err(ShellError::Redirection(format!("{}: ambiguous redirect", target))),
+            },
+            RedirectOp::OutputAndError | RedirectOp::AppendOutputAndError => {
+                let file = open_file(cwd, target, redirection.op)?;
+                self.stderr = Output::File(file.try_clone().map_err(|why| ShellError::io(target, why))?);
+                self.stdout = Output::File(file);
+            }
+            op => self.set_file(fd, open_file(cwd, target, op)?),
+        }
+        Ok(())
+    }
+
+    /// Applies all redirections, in order. Files are opened relative to `cwd`.
+    pub fn redirect_all(&mut self, redirections: &[Redirection], cwd: &Path) -> Result<(), ShellError> {
+        for redirection in redirections {
+            self.redirect(redirection, cwd)?;
+        }
+        Ok(())
+    }
+
+    /// Hands the file descriptors to an external command.
+    /// The ones that were not changed are simply inherited.
+    pub fn apply(self, command: &mut Command) {
+        if !matches!(self.stdin, Input::Stdin) {
+            command.stdin(self.stdin);
+        }
+        if !matches!(self.stdout, Output::Stdout) {
+            command.stdout(self.stdout);
+        }
+        if !matches!(self.stderr, Output::Stderr) {
+            command.stderr(self.stderr);
+        }
+        #[cfg(unix)]
+        if !self.extra.is_empty() {
+            use std::os::unix::io::AsRawFd;
+            use std::os::unix::process::CommandExt;
+            let extra = self.extra;
+            // Safety: only async-signal-safe functions are called between fork and exec.
+            unsafe {
+                command.pre_exec(move || {
+                    for (fd, file) in &extra {
+                        let raw_fd = file.as_raw_fd();
+                        let target_fd = *fd as libc::c_int;
+                        if raw_fd == target_fd {
+                            // dup2 would do nothing, so only clear close-on-exec.
+                            libc::fcntl(raw_fd, libc::F_SETFD, 0);
+                        } else if libc::dup2(raw_fd, target_fd) == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+    }
+}
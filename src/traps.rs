@@ -0,0 +1,139 @@
+use crate::error::ShellError;
+use crate::shared_functions::{process_input, ShellState};
+use crate::signals;
+use std::collections::BTreeMap;
+
+/// When a trap runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    /// When the shell exits.
+    Exit,
+    /// When the shell gets a signal.
+    Signal(i32),
+    /// Before every command.
+    Debug,
+    /// After a command failed, unless its exit status was checked, e.g. by `if` or `&&`.
+    Err,
+}
+
+impl Condition {
+    /// Parses a condition like `EXIT`, `ERR`, `DEBUG`, `INT`, `SIGINT` or `2`.
+    pub fn parse(name: &str) -> Result<Condition, ShellError> {
+        match name.to_ascii_uppercase().as_str() {
+            "EXIT" | "0" => Ok(Condition::Exit),
+            "DEBUG" => Ok(Condition::Debug),
+            "ERR" => Ok(Condition::Err),
+            _ => match signals::number(name) {
+                Some(signal) => Ok(Condition::Signal(signal)),
+                None => Err(ShellError::invalid_argument("trap", name, "invalid signal specification")),
+            },
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Condition::Exit => String::from("EXIT"),
+            Condition::Signal(signal) => match signals::name(signal) {
+                Some(name) => format!("SIG{}", name),
+                None => signal.to_string(),
+            },
+            Condition::Debug => String::from("DEBUG"),
+            Condition::Err => String::from("ERR"),
+        }
+    }
+}
+
+/// The commands set with `trap`.
+#[derive(Debug, Clone, Default)]
+pub struct Traps {
+    /// The command for each condition. An empty command ignores the signal.
+    commands: BTreeMap<Condition, String>,
+    /// Set while a trap runs, so the commands in it don't run the `DEBUG` or `ERR` trap.
+    running: bool,
+    /// Set for the traps of a subshell. It runs in a thread of the shell,
+    /// so its traps can't change how the process handles signals.
+    subshell: bool,
+}
+
+impl Traps {
+    /// Sets the command to run for `condition`, or removes it if `command` is `None`.
+    pub fn set(&mut self, condition: Condition, command: Option<String>) {
+        match condition {
+            Condition::Signal(signal) if !self.subshell => match command.as_deref() {
+                Some("") => signals::ignore(signal),
+                Some(_) => signals::catch(signal),
+                None => signals::reset(signal),
+            },
+            _ => (),
+        }
+        match command {
+            Some(command) => self.commands.insert(condition, command),
+            None => self.commands.remove(&condition),
+        };
+    }
+
+    /// Lists the traps like `trap -p` does, so they can be set again.
+    pub fn list(&self) -> String {
+        let mut output = String::new();
+        for (condition, command) in &self.commands {
+            output.push_str(&format!("trap -- '{}' {}\n", command.replace('\'', "'\\''"), condition.name()));
+        }
+        output
+    }
+
+    /// The traps for a subshell, like `$( )`. Only the ignored signals stay ignored.
+    /// Traps that are set in the subshell are only recorded, so `trap -p` lists them.
+    pub fn for_subshell(&self) -> Traps {
+        let mut commands = self.commands.clone();
+        commands.retain(|_, command| command.is_empty());
+        Traps {
+            commands,
+            running: false,
+            subshell: true,
+        }
+    }
+}
+
+/// Runs the trap for `condition`, if there is one.
+/// `$?` is kept as it was before the trap, unless the trap exits the shell.
+pub fn run_trap(shell_state: &mut ShellState, condition: Condition) {
+    if shell_state.traps.running && matches!(condition, Condition::Debug | Condition::Err) {
+        return;
+    }
+    let command = match shell_state.traps.commands.get(&condition) {
+        Some(command) if !command.is_empty() => command.clone(),
+        _ => return,
+    };
+    let status = shell_state.exit_status;
+    let running = std::mem::replace(&mut shell_state.traps.running, true);
+    process_input(shell_state, &command);
+    shell_state.traps.running = running;
+    shell_state.exit_status = status;
+}
+
+/// Runs the traps for the signals that arrived since they were last checked.
+pub fn run_pending(shell_state: &mut ShellState) {
+    // The signals that arrive belong to the shell, even if a subshell has a trap for them.
+    if shell_state.traps.subshell {
+        return;
+    }
+    let has_signal_traps = shell_state
+        .traps
+        .commands
+        .iter()
+        .any(|(condition, command)| matches!(condition, Condition::Signal(_)) && !command.is_empty());
+    if !has_signal_traps {
+        return;
+    }
+    for signal in signals::take_pending() {
+        run_trap(shell_state, Condition::Signal(signal));
+    }
+}
+
+/// Runs the `EXIT` trap, which only runs once, even if it uses `exit` itself.
+pub fn run_exit_trap(shell_state: &mut ShellState) {
+    if let Some(command) = shell_state.traps.commands.remove(&Condition::Exit) {
+        shell_state.traps.running = true;
+        process_input(shell_state, &command);
+    }
+}
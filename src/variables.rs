@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+
+/// A shell variable. Exported variables are passed to every external command.
+/// The value is `None` for variables that were exported before being set.
+#[derive(Debug, Clone, Default)]
+pub struct Variable {
+    pub value: Option<String>,
+    pub exported: bool,
+}
+
+/// Holds all shell and environment variables.
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    variables: BTreeMap<String, Variable>,
+    /// For every function that is running, the variables it made local,
+    /// with the values they had before, which are restored when it returns.
+    scopes: Vec<Vec<(String, Option<Variable>)>>,
+}
+
+/// Checks if `name` can be used as the name of a variable.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+impl Variables {
+    /// Creates the variable store from the environment the shell was started in,
+    /// where every variable is exported.
+    pub fn from_env() -> Variables {
+        let variables = std::env::vars()
+            .map(|(name, value)| {
+                (name, Variable {
+                    value: Some(value),
+                    exported: true,
+                })
+            })
+            .collect();
+        Variables {
+            variables,
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).and_then(|variable| variable.value.as_deref())
+    }
+
+    /// Sets a variable, keeping it exported if it already was.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.variables.entry(name.to_string()).or_default().value = Some(value.to_string());
+    }
+
+    /// Marks a variable as exported, optionally setting it at the same time.
+    pub fn export(&mut self, name: &str, value: Option<&str>) {
+        let variable = self.variables.entry(name.to_string()).or_default();
+        variable.exported = true;
+        if let Some(value) = value {
+            variable.value = Some(value.to_string());
+        }
+    }
+
+    pub fn unset(&mut self, name: &str) {
+        self.variables.remove(name);
+    }
+
+    /// Starts a new scope for local variables, when a function is called.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    /// Ends the innermost scope, restoring the variables that were made local in it.
+    pub fn pop_scope(&mut self) {
+        let saved = self.scopes.pop().unwrap_or_default();
+        self.restore(saved);
+    }
+
+    /// Sets the variables assigned in front of a builtin or function, like in `FOO=bar env`,
+    /// exported so external commands that it runs get them too.
+    /// Returns the variables as they were before, for `restore` once the command is done.
+    pub fn assign_temporarily(&mut self, assignments: &[(String, String)]) -> Vec<(String, Option<Variable>)> {
+        let mut saved: Vec<(String, Option<Variable>)> = Vec::new();
+        for (name, value) in assignments {
+            if !saved.iter().any(|(saved, _)| saved == name) {
+                saved.push((name.clone(), self.variables.get(name).cloned()));
+            }
+            self.export(name, Some(value));
+        }
+        saved
+    }
+
+    /// Puts back variables saved by `assign_temporarily` or `make_local`.
+    pub fn restore(&mut self, saved: Vec<(String, Option<Variable>)>) {
+        for (name, variable) in saved.into_iter().rev() {
+            match variable {
+                Some(variable) => self.variables.insert(name, variable),
+                None => self.variables.remove(&name),
+            };
+        }
+    }
+
+    /// Makes a variable local to the innermost scope, starting out unset.
+    /// Returns `false` if there is no scope, because no function is running.
+    pub fn make_local(&mut self, name: &str) -> bool {
+        let scope = match self.scopes.last_mut() {
+            Some(scope) => scope,
+            None => return false,
+        };
+        if !scope.iter().any(|(local, _)| local == name) {
+            scope.push((name.to_string(), self.variables.remove(name)));
+        }
+        true
+    }
+
+    /// Returns every variable that is set, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Variable)> {
+        self.variables.iter().map(|(name, variable)| (name.as_str(), variable))
+    }
+
+    /// Returns every exported variable that is set, sorted by name.
+    /// This is the environment that external commands get.
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables.iter().filter(|(_, variable)| variable.exported).filter_map(|(name, variable)| {
+            variable.value.as_deref().map(|value| (name.as_str(), value))
+        })
+    }
+}